@@ -0,0 +1,67 @@
+use log::*;
+use screeps::{find, prelude::*, Part, RoomName};
+
+use crate::creeps::{Creep, Task};
+use crate::expansion;
+
+pub fn get_body(_capacity: u32) -> Vec<Part> {
+    vec![Part::Claim, Part::Move]
+}
+
+pub fn allocate_creeps(creeps: Vec<Creep>) {
+    for creep in creeps {
+        allocate_creep(creep);
+    }
+}
+
+fn allocate_creep(creep: Creep) {
+    if !creep.get_tasks().is_empty() {
+        return;
+    }
+    let room_name = match get_target_room(&creep) {
+        Some(room_name) => room_name,
+        None => return,
+    };
+    if expansion::travel_to_room(&creep, &room_name) {
+        return;
+    }
+
+    let room = screeps::game::rooms::get(creep.room_name());
+    if let Some(controller) = room.and_then(|x| x.controller()) {
+        if controller.my() {
+            retire(&creep);
+            return;
+        }
+        creep.set_output(controller.untyped_id());
+    }
+}
+
+/// Sends a claimer whose work is done home to be recycled.
+fn retire(creep: &Creep) {
+    let spawn = creep
+        .get_home()
+        .and_then(|x| RoomName::new(&x).ok())
+        .and_then(|x| screeps::game::rooms::get(x))
+        .and_then(|x| x.find(find::MY_SPAWNS).into_iter().next());
+    match spawn {
+        Some(spawn) => {
+            info!(
+                "{}: claimed {}, going home",
+                creep.name(),
+                creep.room_name()
+            );
+            creep.push_task(Task::Recycle(spawn.untyped_id()));
+        }
+        None => warn!("{}: no home spawn to retire to", creep.name()),
+    }
+}
+
+/// The room being expanded to when the creep was first allocated.
+fn get_target_room(creep: &Creep) -> Option<String> {
+    if let Ok(Some(room_name)) = creep.memory().string("expansion") {
+        return Some(room_name);
+    }
+    let room_name = expansion::get_expansion()?.target;
+    creep.memory().set("expansion", room_name.as_str());
+    return Some(room_name);
+}
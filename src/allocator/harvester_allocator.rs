@@ -1,21 +1,30 @@
 use log::*;
-use screeps::{find, prelude::*, HasId, Part, RawObjectId, Source};
+use screeps::{find, prelude::*, HasId, Part, RawObjectId, ResourceType, Source};
 use std::collections::HashMap;
 
 use crate::creeps;
+use crate::lifecycle;
+use crate::links;
 
 pub fn get_description(capacity: u32) -> Option<Vec<Part>> {
     if get_target_source().is_none() {
         return None;
     }
 
+    Some(get_body(capacity))
+}
+
+pub fn get_body(capacity: u32) -> Vec<Part> {
     let mut body = vec![Part::Move, Part::Carry];
     let base_body_cost = body.iter().map(|p| p.cost()).sum::<u32>();
     assert!(capacity >= base_body_cost);
     let remaining_capacity = capacity - base_body_cost;
-    let extra_work_parts = remaining_capacity / Part::Work.cost();
-    let extra_carry_parts =
-        (remaining_capacity - (extra_work_parts * Part::Work.cost())) / Part::Carry.cost();
+    let free_parts = super::MAX_BODY_PARTS - body.len() as u32;
+    let extra_work_parts = std::cmp::min(remaining_capacity / Part::Work.cost(), free_parts);
+    let extra_carry_parts = std::cmp::min(
+        (remaining_capacity - (extra_work_parts * Part::Work.cost())) / Part::Carry.cost(),
+        free_parts - extra_work_parts,
+    );
 
     for _ in 0..extra_work_parts {
         body.push(Part::Work);
@@ -25,7 +34,7 @@ pub fn get_description(capacity: u32) -> Option<Vec<Part>> {
         body.push(Part::Carry);
     }
 
-    Some(body)
+    body
 }
 
 pub fn allocate_creeps(creeps: Vec<creeps::Creep>) {
@@ -36,7 +45,9 @@ pub fn allocate_creeps(creeps: Vec<creeps::Creep>) {
 
 fn allocate_creep(creep: creeps::Creep) {
     if creep.get_input::<Source>().is_none() {
-        if let Some(target_source) = get_target_source() {
+        if let Some(predecessor_input) = lifecycle::get_predecessor_input(&creep) {
+            creep.set_input(predecessor_input);
+        } else if let Some(target_source) = get_target_source() {
             creep.set_input(target_source.untyped_id());
         }
     }
@@ -46,8 +57,7 @@ fn allocate_creep(creep: creeps::Creep) {
 
 fn get_target_source() -> Option<Source> {
     let mut harvesters = vec![];
-    for screeps_creep in screeps::game::creeps::values() {
-        let creep = creeps::Creep::new(screeps_creep);
+    for creep in creeps::all() {
         if creep.role == creeps::Role::Harvester {
             harvesters.push(creep);
         }
@@ -73,11 +83,14 @@ fn source_creep_map(creep: Vec<creeps::Creep>) -> HashMap<RawObjectId, Vec<creep
     }
 
     for harvester in creep {
+        // An expiring harvester and its replacement share one slot.
+        if lifecycle::has_live_successor(&harvester) {
+            continue;
+        }
         if let Some(source) = harvester.get_input::<Source>() {
-            result
-                .get_mut(&source.untyped_id())
-                .unwrap()
-                .push(harvester);
+            if let Some(source_harvesters) = result.get_mut(&source.untyped_id()) {
+                source_harvesters.push(harvester);
+            }
         }
     }
 
@@ -101,6 +114,10 @@ fn max_creeps(_source_id: &RawObjectId) -> usize {
 }
 
 fn allocate_creep_output(creep: creeps::Creep) {
+    if let Some(container_id) = get_surplus_output(&creep) {
+        creep.set_output(container_id);
+        return;
+    }
     if let Some(output) = creep.get_output::<screeps::Structure>() {
         if is_valid_output(&output) {
             return;
@@ -117,6 +134,34 @@ fn allocate_creep_output(creep: creeps::Creep) {
     }
 }
 
+/// Once the spawn and extensions are full, energy goes into the source
+/// link, to the upgraders or into storage.
+fn get_surplus_output(creep: &creeps::Creep) -> Option<RawObjectId> {
+    let room = screeps::game::rooms::get(creep.room_name())?;
+    if room.energy_available() < room.energy_capacity_available() {
+        return None;
+    }
+    if let Some(link) = creep
+        .get_input::<Source>()
+        .and_then(|x| links::get_source_link(&x))
+    {
+        if link.store_free_capacity(Some(ResourceType::Energy)) > 0 {
+            return Some(link.untyped_id());
+        }
+    }
+    if let Some(container) = super::upgrader_allocator::get_controller_container(&room) {
+        if container.store_free_capacity(None) > 0 {
+            return Some(container.untyped_id());
+        }
+    }
+    if let Some(storage) = room.storage() {
+        if storage.store_free_capacity(Some(ResourceType::Energy)) > 0 {
+            return Some(storage.untyped_id());
+        }
+    }
+    return None;
+}
+
 fn is_valid_output(structure: &screeps::Structure) -> bool {
     if let Some(has_store) = structure.as_has_energy_for_spawn() {
         if has_store.store_free_capacity(None) <= 0 {
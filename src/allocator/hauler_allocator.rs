@@ -0,0 +1,67 @@
+use screeps::{prelude::*, Part, RawObjectId, RoomName};
+
+use crate::creeps::{Creep, Task};
+use crate::factory;
+use crate::labs;
+use crate::links;
+use crate::power;
+
+const MAX_CARRY_PARTS: u32 = 10;
+
+pub fn get_body(capacity: u32) -> Vec<Part> {
+    let part_set_cost = Part::Carry.cost() + Part::Move.cost();
+    let part_sets = std::cmp::min(capacity / part_set_cost, MAX_CARRY_PARTS).max(1);
+    let mut body = vec![];
+    for _ in 0..part_sets {
+        body.push(Part::Carry);
+        body.push(Part::Move);
+    }
+    body
+}
+
+pub fn allocate_creeps(creeps: Vec<Creep>) {
+    for creep in creeps {
+        allocate_creep(creep);
+    }
+}
+
+/// Haulers take one job at a time from their home room, labs first, then
+/// the storage link, the factory and the power spawn, dropping
+/// off anything left over before the next.
+fn allocate_creep(creep: Creep) {
+    if creep.is_spawning() || !creep.get_tasks().is_empty() {
+        return;
+    }
+    let room = match creep
+        .get_home()
+        .and_then(|x| RoomName::new(&x).ok())
+        .and_then(|x| screeps::game::rooms::get(x))
+    {
+        Some(room) => room,
+        None => return,
+    };
+
+    if !creep.is_empty() {
+        if let Some(store_id) = get_store_id(&room) {
+            creep.push_task(Task::Transfer(store_id));
+        }
+        return;
+    }
+    let job = labs::get_hauler_jobs(&room)
+        .into_iter()
+        .chain(links::get_hauler_jobs(&room))
+        .chain(factory::get_hauler_jobs(&room))
+        .chain(power::get_hauler_jobs(&room))
+        .next();
+    if let Some((from, resource, to)) = job {
+        creep.push_task(Task::WithdrawResource(from, resource));
+        creep.push_task(Task::Transfer(to));
+    }
+}
+
+fn get_store_id(room: &screeps::Room) -> Option<RawObjectId> {
+    if let Some(terminal) = room.terminal() {
+        return Some(terminal.untyped_id());
+    }
+    return room.storage().map(|x| x.untyped_id());
+}
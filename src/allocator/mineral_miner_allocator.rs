@@ -0,0 +1,109 @@
+use log::*;
+use screeps::{find, prelude::*, Mineral, Part, RawObjectId, StructureType};
+use stdweb::{js, unstable::TryInto, Reference};
+
+use crate::creeps::{self, Creep, Role};
+
+/// Beyond this the extractor cooldown, not the number of WORK parts, limits
+/// how fast a mineral is mined.
+const MAX_WORK_PARTS: u32 = 20;
+
+pub fn get_description(capacity: u32) -> Option<Vec<Part>> {
+    if get_target_mineral().is_none() {
+        return None;
+    }
+
+    Some(get_body(capacity))
+}
+
+pub fn get_body(capacity: u32) -> Vec<Part> {
+    let mut body = vec![Part::Carry, Part::Carry, Part::Move];
+    let base_body_cost = body.iter().map(|p| p.cost()).sum::<u32>();
+    let remaining_capacity = capacity.saturating_sub(base_body_cost);
+    let work_move_cost = Part::Work.cost() + Part::Move.cost();
+    let work_parts = std::cmp::min(remaining_capacity / work_move_cost, MAX_WORK_PARTS);
+    for _ in 0..work_parts {
+        body.push(Part::Work);
+        body.push(Part::Move);
+    }
+    body
+}
+
+pub fn allocate_creeps(creeps: Vec<Creep>) {
+    for creep in creeps {
+        allocate_creep(creep);
+    }
+}
+
+fn allocate_creep(creep: Creep) {
+    if creep.get_input::<Mineral>().is_none() {
+        if let Some(mineral) = get_target_mineral() {
+            creep.set_input(mineral.untyped_id());
+        }
+    }
+
+    if let Some(mineral) = creep.get_input::<Mineral>() {
+        if let Some(output_id) = get_mineral_output(&mineral) {
+            creep.set_output(output_id);
+        } else {
+            warn!("No storage or terminal for minerals.");
+        }
+    }
+}
+
+/// A mineral with a built extractor and nobody mining it.
+fn get_target_mineral() -> Option<Mineral> {
+    let assigned: Vec<RawObjectId> = creeps::all()
+        .iter()
+        .filter(|x| x.role == Role::MineralMiner)
+        .filter_map(|x| x.get_stored_id("input"))
+        .collect();
+
+    for room in screeps::game::rooms::values() {
+        match room.controller() {
+            Some(controller) if controller.my() => (),
+            _ => continue,
+        }
+        if get_mineral_output_room(&room).is_none() {
+            continue;
+        }
+        for mineral in room.find(find::MINERALS) {
+            if get_mineral_amount(&mineral) > 0
+                && has_extractor(&room)
+                && !assigned.contains(&mineral.untyped_id())
+            {
+                return Some(mineral);
+            }
+        }
+    }
+    return None;
+}
+
+/// The bindings read `mineralAmount` as a density, so read it directly.
+fn get_mineral_amount(mineral: &Mineral) -> u32 {
+    let reference: &Reference = mineral.as_ref();
+    let amount = js! {
+        return @{reference}.mineralAmount;
+    };
+    return amount.try_into().unwrap_or(0);
+}
+
+fn has_extractor(room: &screeps::Room) -> bool {
+    room.find(find::MY_STRUCTURES)
+        .iter()
+        .any(|x| x.structure_type() == StructureType::Extractor)
+}
+
+fn get_mineral_output(mineral: &Mineral) -> Option<RawObjectId> {
+    get_mineral_output_room(&mineral.room())
+}
+
+fn get_mineral_output_room(room: &screeps::Room) -> Option<RawObjectId> {
+    if let Some(storage) = room.storage() {
+        return Some(storage.untyped_id());
+    }
+    if let Some(terminal) = room.terminal() {
+        return Some(terminal.untyped_id());
+    }
+    return None;
+}
@@ -1,16 +1,36 @@
+use super::boosts;
 use super::creeps;
 use log::*;
 use screeps::Part;
 use std::collections::HashMap;
 
+mod claimer_allocator;
 mod harvester_allocator;
+mod hauler_allocator;
+mod mineral_miner_allocator;
+mod pickup;
+mod pioneer_allocator;
+mod power_attacker_allocator;
+mod power_carrier_allocator;
+mod power_healer_allocator;
+mod remote_hauler_allocator;
+mod remote_miner_allocator;
+mod reserver_allocator;
+mod scout_allocator;
+mod upgrader_allocator;
 mod worker_allocator;
 
+/// MAX_CREEP_SIZE
+const MAX_BODY_PARTS: u32 = 50;
+
 pub fn allocate_creeps() {
     debug!("{}:{}: allocate creeps", std::file!(), std::line!());
     let mut role_map = HashMap::new();
-    for screeps_creep in screeps::game::creeps::values() {
-        let creep = creeps::Creep::new(screeps_creep);
+    for creep in creeps::all() {
+        // New creeps get their boosts before starting on their role.
+        if boosts::allocate_boosts(&creep) {
+            continue;
+        }
         role_map
             .entry(creep.role.clone())
             .or_insert(vec![])
@@ -23,15 +43,85 @@ pub fn allocate_creeps() {
     if let Some(workers) = role_map.remove(&creeps::Role::Worker) {
         worker_allocator::allocate_creeps(workers);
     }
+    if let Some(mineral_miners) = role_map.remove(&creeps::Role::MineralMiner) {
+        mineral_miner_allocator::allocate_creeps(mineral_miners);
+    }
+    if let Some(reservers) = role_map.remove(&creeps::Role::Reserver) {
+        reserver_allocator::allocate_creeps(reservers);
+    }
+    if let Some(remote_miners) = role_map.remove(&creeps::Role::RemoteMiner) {
+        remote_miner_allocator::allocate_creeps(remote_miners);
+    }
+    if let Some(remote_haulers) = role_map.remove(&creeps::Role::RemoteHauler) {
+        remote_hauler_allocator::allocate_creeps(remote_haulers);
+    }
+    if let Some(scouts) = role_map.remove(&creeps::Role::Scout) {
+        scout_allocator::allocate_creeps(scouts);
+    }
+    if let Some(claimers) = role_map.remove(&creeps::Role::Claimer) {
+        claimer_allocator::allocate_creeps(claimers);
+    }
+    if let Some(pioneers) = role_map.remove(&creeps::Role::Pioneer) {
+        pioneer_allocator::allocate_creeps(pioneers);
+    }
+    if let Some(upgraders) = role_map.remove(&creeps::Role::Upgrader) {
+        upgrader_allocator::allocate_creeps(upgraders);
+    }
+    if let Some(haulers) = role_map.remove(&creeps::Role::Hauler) {
+        hauler_allocator::allocate_creeps(haulers);
+    }
+    if let Some(power_attackers) = role_map.remove(&creeps::Role::PowerAttacker) {
+        power_attacker_allocator::allocate_creeps(power_attackers);
+    }
+    if let Some(power_healers) = role_map.remove(&creeps::Role::PowerHealer) {
+        power_healer_allocator::allocate_creeps(power_healers);
+    }
+    if let Some(power_carriers) = role_map.remove(&creeps::Role::PowerCarrier) {
+        power_carrier_allocator::allocate_creeps(power_carriers);
+    }
+}
+
+pub fn get_body(role: &creeps::Role, capacity: u32) -> Vec<Part> {
+    return match role {
+        creeps::Role::Harvester => harvester_allocator::get_body(capacity),
+        creeps::Role::Worker => worker_allocator::get_body(capacity),
+        creeps::Role::MineralMiner => mineral_miner_allocator::get_body(capacity),
+        creeps::Role::Reserver => reserver_allocator::get_body(capacity),
+        creeps::Role::RemoteMiner => remote_miner_allocator::get_body(capacity),
+        creeps::Role::RemoteHauler => remote_hauler_allocator::get_body(capacity),
+        creeps::Role::Scout => scout_allocator::get_body(capacity),
+        creeps::Role::Claimer => claimer_allocator::get_body(capacity),
+        creeps::Role::Pioneer => pioneer_allocator::get_body(capacity),
+        creeps::Role::Upgrader => upgrader_allocator::get_body(capacity),
+        creeps::Role::Hauler => hauler_allocator::get_body(capacity),
+        creeps::Role::PowerAttacker => power_attacker_allocator::get_body(capacity),
+        creeps::Role::PowerHealer => power_healer_allocator::get_body(capacity),
+        creeps::Role::PowerCarrier => power_carrier_allocator::get_body(capacity),
+    };
 }
 
 pub fn get_spawn_target(capacity: u32) -> Option<(Vec<Part>, creeps::Role)> {
-    const DESCRIPTORS: [(creeps::Role, &dyn Fn(u32) -> Option<Vec<Part>>); 2] = [
+    const DESCRIPTORS: [(creeps::Role, &dyn Fn(u32) -> Option<Vec<Part>>); 8] = [
         (
             creeps::Role::Harvester,
             &harvester_allocator::get_description,
         ),
         (creeps::Role::Worker, &worker_allocator::get_description),
+        (creeps::Role::Upgrader, &upgrader_allocator::get_description),
+        (
+            creeps::Role::MineralMiner,
+            &mineral_miner_allocator::get_description,
+        ),
+        (creeps::Role::Reserver, &reserver_allocator::get_description),
+        (
+            creeps::Role::RemoteMiner,
+            &remote_miner_allocator::get_description,
+        ),
+        (
+            creeps::Role::RemoteHauler,
+            &remote_hauler_allocator::get_description,
+        ),
+        (creeps::Role::Scout, &scout_allocator::get_description),
     ];
     for (role, description_func) in DESCRIPTORS.iter() {
         if let Some(description) = description_func(capacity) {
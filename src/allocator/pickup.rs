@@ -0,0 +1,96 @@
+use crate::creeps::Creep;
+use screeps::{find, prelude::*, RawObjectId, Resource, ResourceType};
+
+/// Ignore piles that would be smaller than this by the time we arrive.
+const MINIMUM_PICKUP_AMOUNT: u32 = 50;
+/// ENERGY_DECAY
+const ENERGY_DECAY: u32 = 1000;
+
+struct Candidate {
+    id: RawObjectId,
+    amount: u32,
+    distance: u32,
+    /// Energy lost per tick while it waits to be collected.
+    decay_per_tick: u32,
+    ticks_to_decay: Option<u32>,
+}
+
+impl Candidate {
+    fn amount_on_arrival(&self) -> u32 {
+        if let Some(ticks_to_decay) = self.ticks_to_decay {
+            if ticks_to_decay <= self.distance {
+                return 0;
+            }
+        }
+        self.amount
+            .saturating_sub(self.decay_per_tick * self.distance)
+    }
+
+    fn score(&self) -> u32 {
+        self.amount_on_arrival() / (self.distance + 1)
+    }
+}
+
+/// Chooses dropped energy, a tombstone or a ruin for the creep to empty,
+/// weighing what will be left on arrival against the walk there.
+pub fn find_pickup_target(creep: &Creep) -> Option<RawObjectId> {
+    let candidates = find_candidates(creep);
+
+    if let Some(current_id) = creep.get_stored_id("input") {
+        if let Some(current) = candidates.iter().find(|x| x.id == current_id) {
+            if current.amount_on_arrival() >= MINIMUM_PICKUP_AMOUNT {
+                return Some(current_id);
+            }
+        }
+    }
+
+    return candidates
+        .iter()
+        .filter(|x| x.amount_on_arrival() >= MINIMUM_PICKUP_AMOUNT)
+        .max_by_key(|x| x.score())
+        .map(|x| x.id);
+}
+
+fn find_candidates(creep: &Creep) -> Vec<Candidate> {
+    let room = match screeps::game::rooms::get(creep.room_name()) {
+        Some(room) => room,
+        None => return vec![],
+    };
+
+    let mut result = vec![];
+    for resource in room.find(find::DROPPED_RESOURCES) {
+        if resource.resource_type() != ResourceType::Energy {
+            continue;
+        }
+        result.push(Candidate {
+            id: resource.untyped_id(),
+            amount: resource.amount(),
+            distance: creep.get_range_to(&resource),
+            decay_per_tick: get_dropped_decay(&resource),
+            ticks_to_decay: None,
+        });
+    }
+    for tombstone in room.find(find::TOMBSTONES) {
+        result.push(Candidate {
+            id: tombstone.untyped_id(),
+            amount: tombstone.store_of(ResourceType::Energy),
+            distance: creep.get_range_to(&tombstone),
+            decay_per_tick: 0,
+            ticks_to_decay: Some(tombstone.ticks_to_decay()),
+        });
+    }
+    for ruin in room.find(find::RUINS) {
+        result.push(Candidate {
+            id: ruin.untyped_id(),
+            amount: ruin.store_of(ResourceType::Energy),
+            distance: creep.get_range_to(&ruin),
+            decay_per_tick: 0,
+            ticks_to_decay: Some(ruin.ticks_to_decay()),
+        });
+    }
+    return result;
+}
+
+fn get_dropped_decay(resource: &Resource) -> u32 {
+    (resource.amount() + ENERGY_DECAY - 1) / ENERGY_DECAY
+}
@@ -0,0 +1,80 @@
+use screeps::{find, prelude::*, Part, RawObjectId, Source, StructureType};
+
+use crate::creeps::{self, Creep, Role};
+use crate::expansion;
+
+const MAX_PART_SETS: u32 = 8;
+
+/// Whole work, carry and move sets only, so the body never costs more than
+/// the parent room can spend.
+pub fn get_body(capacity: u32) -> Vec<Part> {
+    let part_set = [Part::Work, Part::Carry, Part::Move];
+    let part_set_cost: u32 = part_set.iter().map(|part| part.cost()).sum();
+    let part_sets = std::cmp::min(capacity / part_set_cost, MAX_PART_SETS).max(1);
+    let mut body = vec![];
+    for _ in 0..part_sets {
+        body.extend(part_set.iter().cloned());
+    }
+    body
+}
+
+pub fn allocate_creeps(creeps: Vec<Creep>) {
+    for creep in creeps {
+        allocate_creep(creep);
+    }
+}
+
+/// Pioneers harvest for themselves in the new room and build its spawn,
+/// then keep the controller from downgrading until they expire.
+fn allocate_creep(creep: Creep) {
+    let room_name = match get_target_room(&creep) {
+        Some(room_name) => room_name,
+        None => return,
+    };
+    if expansion::travel_to_room(&creep, &room_name) {
+        return;
+    }
+    let room = match screeps::game::rooms::get(creep.room_name()) {
+        Some(room) => room,
+        None => return,
+    };
+
+    if creep.get_input::<Source>().is_none() {
+        if let Some(source_id) = get_least_used_source(&room) {
+            creep.set_input(source_id);
+        }
+    }
+
+    let sites = room.find(find::MY_CONSTRUCTION_SITES);
+    let site = sites
+        .iter()
+        .find(|x| x.structure_type() == StructureType::Spawn)
+        .or(sites.first());
+    if let Some(site) = site {
+        creep.set_output(site.untyped_id());
+    } else if let Some(controller) = room.controller() {
+        creep.set_output(controller.untyped_id());
+    }
+}
+
+fn get_least_used_source(room: &screeps::Room) -> Option<RawObjectId> {
+    let assigned: Vec<RawObjectId> = creeps::all()
+        .iter()
+        .filter(|x| x.role == Role::Pioneer)
+        .filter_map(|x| x.get_stored_id("input"))
+        .collect();
+    room.find(find::SOURCES)
+        .iter()
+        .map(|x| x.untyped_id())
+        .min_by_key(|x| assigned.iter().filter(|assigned| *assigned == x).count())
+}
+
+/// The room being expanded to when the creep was first allocated.
+fn get_target_room(creep: &Creep) -> Option<String> {
+    if let Ok(Some(room_name)) = creep.memory().string("expansion") {
+        return Some(room_name);
+    }
+    let room_name = expansion::get_expansion()?.target;
+    creep.memory().set("expansion", room_name.as_str());
+    return Some(room_name);
+}
@@ -0,0 +1,73 @@
+use screeps::{prelude::*, Part};
+
+use crate::creeps::{Creep, Task};
+use crate::expansion;
+use crate::power_banks;
+
+const MAX_ATTACK_PARTS: u32 = 25;
+/// Healers heal once they are this close.
+const HEALER_RANGE: u32 = 2;
+
+/// As many attack parts as a healer from the same room can make up for
+/// the damage the bank hits back with.
+pub fn get_body(capacity: u32) -> Vec<Part> {
+    let heal = super::power_healer_allocator::get_heal_parts(capacity) * power_banks::HEAL_POWER;
+    let hit_back_per_part = power_banks::ATTACK_POWER * power_banks::HIT_BACK_PERCENT / 100;
+    let part_set_cost = Part::Attack.cost() + Part::Move.cost();
+    let attack_parts = (heal / hit_back_per_part)
+        .min(capacity / part_set_cost)
+        .min(MAX_ATTACK_PARTS)
+        .max(1);
+    let mut body = vec![];
+    for _ in 0..attack_parts {
+        body.push(Part::Move);
+    }
+    for _ in 0..attack_parts {
+        body.push(Part::Attack);
+    }
+    body
+}
+
+pub fn allocate_creeps(creeps: Vec<Creep>) {
+    for (index, creep) in creeps.into_iter().enumerate() {
+        allocate_creep(creep, index);
+    }
+}
+
+/// Attackers only hit the bank with their healer beside them, and wait out
+/// of its reach otherwise.
+fn allocate_creep(creep: Creep, index: usize) {
+    if creep.is_spawning() || !creep.get_tasks().is_empty() {
+        return;
+    }
+    let room_name = match power_banks::get_target_room(&creep) {
+        Some(room_name) => room_name,
+        None => {
+            power_banks::retire(&creep);
+            return;
+        }
+    };
+    if expansion::travel_to_room(&creep, &room_name) {
+        return;
+    }
+    let room = match screeps::game::rooms::get(creep.room_name()) {
+        Some(room) => room,
+        None => return,
+    };
+    let power_bank = match power_banks::get_power_bank(&room) {
+        Some(power_bank) => power_bank,
+        None => {
+            power_banks::retire(&creep);
+            return;
+        }
+    };
+
+    let is_healed = power_banks::get_healer(&creep)
+        .map_or(false, |x| creep.get_range_to(&x.pos()) <= HEALER_RANGE);
+    if is_healed {
+        creep.push_task(Task::Attack(power_bank.untyped_id()));
+    } else if let Some(position) = power_banks::get_waiting_position(&room, power_bank.pos(), index)
+    {
+        creep.push_task(Task::MoveTo(position));
+    }
+}
@@ -0,0 +1,84 @@
+use screeps::{prelude::*, Part, RawObjectId, RoomName};
+
+use crate::creeps::{Creep, Task};
+use crate::expansion;
+use crate::power_banks;
+
+const MAX_CARRY_PARTS: u32 = 25;
+
+pub fn get_body(capacity: u32) -> Vec<Part> {
+    let part_set_cost = Part::Carry.cost() + Part::Move.cost();
+    let part_sets = std::cmp::min(capacity / part_set_cost, MAX_CARRY_PARTS).max(1);
+    let mut body = vec![];
+    for _ in 0..part_sets {
+        body.push(Part::Carry);
+        body.push(Part::Move);
+    }
+    body
+}
+
+pub fn allocate_creeps(creeps: Vec<Creep>) {
+    for (index, creep) in creeps.into_iter().enumerate() {
+        allocate_creep(creep, index);
+    }
+}
+
+/// Carriers wait near the bank until it breaks, pick up the dropped power
+/// and take it home. Once none is left they are recycled.
+fn allocate_creep(creep: Creep, index: usize) {
+    if creep.is_spawning() || !creep.get_tasks().is_empty() {
+        return;
+    }
+    let home_store_id = get_home_store_id(&creep);
+    if let Some(home_store_id) = home_store_id {
+        creep.set_output(home_store_id);
+    }
+    let room_name = power_banks::get_target_room(&creep);
+    let is_in_room = room_name.as_deref() == Some(creep.room_name().to_string().as_str());
+    // Loaded carriers outside the bank's room are on their way home.
+    if !creep.is_empty() && !is_in_room {
+        return;
+    }
+    let room_name = match room_name {
+        Some(room_name) => room_name,
+        None => {
+            power_banks::retire(&creep);
+            return;
+        }
+    };
+    if expansion::travel_to_room(&creep, &room_name) {
+        return;
+    }
+    let room = match screeps::game::rooms::get(creep.room_name()) {
+        Some(room) => room,
+        None => return,
+    };
+
+    if let Some(resource) = power_banks::get_dropped_power(&room)
+        .into_iter()
+        .min_by_key(|x| creep.get_range_to(x))
+    {
+        creep.set_input(resource.untyped_id());
+        return;
+    }
+    if let Some(power_bank) = power_banks::get_power_bank(&room) {
+        if let Some(position) = power_banks::get_waiting_position(&room, power_bank.pos(), index) {
+            creep.push_task(Task::MoveTo(position));
+        }
+        return;
+    }
+    match home_store_id {
+        Some(home_store_id) if !creep.is_empty() => creep.push_task(Task::Transfer(home_store_id)),
+        _ => power_banks::retire(&creep),
+    }
+}
+
+/// Storage if the home room has one, otherwise its terminal.
+fn get_home_store_id(creep: &Creep) -> Option<RawObjectId> {
+    let home = RoomName::new(&creep.get_home()?).ok()?;
+    let room = screeps::game::rooms::get(home)?;
+    if let Some(storage) = room.storage() {
+        return Some(storage.untyped_id());
+    }
+    return room.terminal().map(|x| x.untyped_id());
+}
@@ -0,0 +1,66 @@
+use screeps::{prelude::*, Part};
+
+use crate::creeps::{Creep, Task};
+use crate::expansion;
+use crate::power_banks;
+
+const MAX_HEAL_PARTS: u32 = 25;
+
+pub fn get_heal_parts(capacity: u32) -> u32 {
+    let part_set_cost = Part::Heal.cost() + Part::Move.cost();
+    return std::cmp::min(capacity / part_set_cost, MAX_HEAL_PARTS).max(1);
+}
+
+pub fn get_body(capacity: u32) -> Vec<Part> {
+    let heal_parts = get_heal_parts(capacity);
+    let mut body = vec![];
+    for _ in 0..heal_parts {
+        body.push(Part::Move);
+    }
+    for _ in 0..heal_parts {
+        body.push(Part::Heal);
+    }
+    body
+}
+
+pub fn allocate_creeps(creeps: Vec<Creep>) {
+    for (index, creep) in creeps.into_iter().enumerate() {
+        allocate_creep(creep, index);
+    }
+}
+
+/// Healers follow their attacker and heal it every tick. Until one is
+/// spawned they wait near the bank.
+fn allocate_creep(creep: Creep, index: usize) {
+    if creep.is_spawning() || !creep.get_tasks().is_empty() {
+        return;
+    }
+    let room_name = match power_banks::get_target_room(&creep) {
+        Some(room_name) => room_name,
+        None => {
+            power_banks::retire(&creep);
+            return;
+        }
+    };
+    if let Some(partner) = power_banks::get_partner(&creep) {
+        creep.push_task(Task::Heal(partner.id()));
+        return;
+    }
+    if expansion::travel_to_room(&creep, &room_name) {
+        return;
+    }
+    let room = match screeps::game::rooms::get(creep.room_name()) {
+        Some(room) => room,
+        None => return,
+    };
+    match power_banks::get_power_bank(&room) {
+        Some(power_bank) => {
+            if let Some(position) =
+                power_banks::get_waiting_position(&room, power_bank.pos(), index)
+            {
+                creep.push_task(Task::MoveTo(position));
+            }
+        }
+        None => power_banks::retire(&creep),
+    }
+}
@@ -0,0 +1,93 @@
+use screeps::{prelude::*, Part, RawObjectId, ResourceType};
+
+use crate::creeps::{Creep, Role};
+use crate::remote;
+
+const MAX_PART_SETS: u32 = 10;
+/// Tiles of distance one hauler can keep up with for a single source.
+const DISTANCE_PER_HAULER: u32 = 50;
+
+pub fn get_description(capacity: u32) -> Option<Vec<Part>> {
+    if get_target_room().is_none() {
+        return None;
+    }
+
+    Some(get_body(capacity))
+}
+
+pub fn get_body(capacity: u32) -> Vec<Part> {
+    let part_set = [Part::Carry, Part::Carry, Part::Move];
+    let part_set_cost: u32 = part_set.iter().map(|part| part.cost()).sum();
+    let part_sets = std::cmp::min(capacity / part_set_cost, MAX_PART_SETS).max(1);
+    let mut body = vec![];
+    for _ in 0..part_sets {
+        body.extend(part_set.iter().cloned());
+    }
+    body
+}
+
+pub fn allocate_creeps(creeps: Vec<Creep>) {
+    for creep in creeps {
+        allocate_creep(creep);
+    }
+}
+
+fn allocate_creep(creep: Creep) {
+    if remote::retire_if_inactive(&creep) {
+        return;
+    }
+    if remote::get_assigned_room(&creep).is_none() {
+        match get_target_room() {
+            Some(room_name) => remote::assign_room(&creep, &room_name),
+            None => return,
+        }
+    }
+    if let Some(output_id) = get_home_output(&creep) {
+        creep.set_output(output_id);
+    }
+    if remote::travel_to_assigned_room(&creep) {
+        return;
+    }
+
+    if let Some(input_id) = get_fullest_container(&creep) {
+        creep.set_input(input_id);
+    }
+}
+
+fn get_fullest_container(creep: &Creep) -> Option<RawObjectId> {
+    let room = remote::get_visible_room(&remote::get_assigned_room(creep)?)?;
+    let container = remote::get_source_containers(&room)
+        .into_iter()
+        .max_by_key(|x| x.store_of(ResourceType::Energy))?;
+    return Some(container.untyped_id());
+}
+
+/// Storage if the home room has one, otherwise a spawn or extension with
+/// room for energy.
+fn get_home_output(creep: &Creep) -> Option<RawObjectId> {
+    let home = remote::get_visible_room(&creep.get_home()?)?;
+    if let Some(storage) = home.storage() {
+        return Some(storage.untyped_id());
+    }
+    let output = home.find(screeps::find::STRUCTURES).into_iter().find(|x| {
+        x.as_has_energy_for_spawn().map_or(false, |x| {
+            x.store_free_capacity(Some(ResourceType::Energy)) > 0
+        })
+    })?;
+    return Some(output.untyped_id());
+}
+
+/// An active remote room with fewer haulers than its distance needs.
+fn get_target_room() -> Option<String> {
+    for remote_room in remote::get_active_rooms() {
+        let distance = match remote_room.distance {
+            Some(distance) => distance,
+            None => continue,
+        };
+        let needed = remote_room.sources * (1 + distance / DISTANCE_PER_HAULER);
+        if (remote::count_assigned(&Role::RemoteHauler, &remote_room.name) as u32) < needed {
+            return Some(remote_room.name);
+        }
+    }
+    return None;
+}
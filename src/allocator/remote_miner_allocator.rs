@@ -0,0 +1,85 @@
+use screeps::{find, prelude::*, Part, RawObjectId, Source};
+
+use crate::creeps::{self, Creep, Role};
+use crate::remote;
+
+/// Five WORK parts empty a reserved source before it regenerates. They come
+/// in pairs, so the sixth makes up for time lost travelling.
+const WORK_PARTS: u32 = 6;
+
+pub fn get_description(capacity: u32) -> Option<Vec<Part>> {
+    if get_target_source().is_none() {
+        return None;
+    }
+
+    Some(get_body(capacity))
+}
+
+pub fn get_body(capacity: u32) -> Vec<Part> {
+    let mut body = vec![Part::Carry, Part::Move];
+    let base_body_cost = body.iter().map(|p| p.cost()).sum::<u32>();
+    let part_set_cost = 2 * Part::Work.cost() + Part::Move.cost();
+    let part_sets = std::cmp::min(
+        capacity.saturating_sub(base_body_cost) / part_set_cost,
+        WORK_PARTS / 2,
+    );
+    for _ in 0..part_sets {
+        body.push(Part::Work);
+        body.push(Part::Work);
+        body.push(Part::Move);
+    }
+    body
+}
+
+pub fn allocate_creeps(creeps: Vec<Creep>) {
+    for creep in creeps {
+        allocate_creep(creep);
+    }
+}
+
+fn allocate_creep(creep: Creep) {
+    if remote::retire_if_inactive(&creep) {
+        return;
+    }
+    if remote::get_assigned_room(&creep).is_none() {
+        match get_target_source() {
+            Some((room_name, source_id)) => {
+                remote::assign_room(&creep, &room_name);
+                creep.set_input(source_id);
+            }
+            None => return,
+        }
+    }
+    if remote::travel_to_assigned_room(&creep) {
+        return;
+    }
+
+    // Harvest into the container beside the source, or build it first.
+    if let Some(source) = creep.get_input::<Source>() {
+        if let Some(container_id) = remote::get_source_container(&source) {
+            creep.set_output(container_id);
+        }
+    }
+}
+
+/// A source in a visible, active remote room that has no miner.
+fn get_target_source() -> Option<(String, RawObjectId)> {
+    let assigned: Vec<RawObjectId> = creeps::all()
+        .iter()
+        .filter(|x| x.role == Role::RemoteMiner)
+        .filter_map(|x| x.get_stored_id("input"))
+        .collect();
+
+    for remote_room in remote::get_active_rooms() {
+        let room = match remote::get_visible_room(&remote_room.name) {
+            Some(room) => room,
+            None => continue,
+        };
+        for source in room.find(find::SOURCES) {
+            if !assigned.contains(&source.untyped_id()) {
+                return Some((remote_room.name, source.untyped_id()));
+            }
+        }
+    }
+    return None;
+}
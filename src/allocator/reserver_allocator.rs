@@ -0,0 +1,70 @@
+use screeps::{prelude::*, Part};
+
+use crate::creeps::{Creep, Role};
+use crate::remote;
+
+/// Reservations are topped up before they fall below this.
+const MINIMUM_RESERVATION: u32 = 3000;
+const MAX_CLAIM_PARTS: u32 = 2;
+
+pub fn get_description(capacity: u32) -> Option<Vec<Part>> {
+    if capacity < Part::Claim.cost() + Part::Move.cost() || get_target_room().is_none() {
+        return None;
+    }
+
+    Some(get_body(capacity))
+}
+
+pub fn get_body(capacity: u32) -> Vec<Part> {
+    let part_set_cost = Part::Claim.cost() + Part::Move.cost();
+    let part_sets = std::cmp::min(capacity / part_set_cost, MAX_CLAIM_PARTS).max(1);
+    let mut body = vec![];
+    for _ in 0..part_sets {
+        body.push(Part::Claim);
+        body.push(Part::Move);
+    }
+    body
+}
+
+pub fn allocate_creeps(creeps: Vec<Creep>) {
+    for creep in creeps {
+        allocate_creep(creep);
+    }
+}
+
+fn allocate_creep(creep: Creep) {
+    if remote::retire_if_inactive(&creep) {
+        return;
+    }
+    if remote::get_assigned_room(&creep).is_none() {
+        match get_target_room() {
+            Some(room_name) => remote::assign_room(&creep, &room_name),
+            None => return,
+        }
+    }
+    if remote::travel_to_assigned_room(&creep) {
+        return;
+    }
+
+    let room = remote::get_assigned_room(&creep).and_then(|x| remote::get_visible_room(&x));
+    if let Some(controller) = room.and_then(|x| x.controller()) {
+        creep.set_output(controller.untyped_id());
+    }
+}
+
+/// An active remote room without a reserver whose reservation is running low.
+fn get_target_room() -> Option<String> {
+    for remote_room in remote::get_active_rooms() {
+        if remote::count_assigned(&Role::Reserver, &remote_room.name) > 0 {
+            continue;
+        }
+        let reservation = remote::get_visible_room(&remote_room.name)
+            .and_then(|x| x.controller())
+            .and_then(|x| x.reservation())
+            .map_or(0, |x| x.ticks_to_end);
+        if reservation < MINIMUM_RESERVATION {
+            return Some(remote_room.name);
+        }
+    }
+    return None;
+}
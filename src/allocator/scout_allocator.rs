@@ -0,0 +1,62 @@
+use log::*;
+use screeps::{Part, Position, RoomName};
+
+use crate::creeps::{self, Creep, Role, Task};
+use crate::intel;
+
+/// Scouts stay within this many rooms of home.
+const SCOUT_RANGE: u32 = 3;
+
+pub fn get_description(capacity: u32) -> Option<Vec<Part>> {
+    let has_scout = creeps::all().iter().any(|x| x.role == Role::Scout);
+    if has_scout {
+        return None;
+    }
+
+    Some(get_body(capacity))
+}
+
+pub fn get_body(_capacity: u32) -> Vec<Part> {
+    vec![Part::Move]
+}
+
+pub fn allocate_creeps(creeps: Vec<Creep>) {
+    for creep in creeps {
+        allocate_creep(creep);
+    }
+}
+
+fn allocate_creep(creep: Creep) {
+    if creep.is_spawning() || !creep.get_tasks().is_empty() {
+        return;
+    }
+
+    let home = creep
+        .get_home()
+        .and_then(|x| RoomName::new(&x).ok())
+        .unwrap_or(creep.room_name());
+    if let Some(room_name) = get_next_room(creep.room_name(), home) {
+        debug!("{}: scouting {}", creep.name(), room_name);
+        creep.push_task(Task::MoveToRoom(Position::new(25, 25, room_name)));
+    }
+}
+
+/// The neighbor seen longest ago, preferring rooms within range of home and
+/// avoiding rooms known to be defended.
+fn get_next_room(current: RoomName, home: RoomName) -> Option<RoomName> {
+    screeps::game::map::describe_exits(current)
+        .values()
+        .filter_map(|x| RoomName::new(x).ok())
+        .min_by_key(|x| {
+            let is_out_of_range =
+                screeps::game::map::get_room_linear_distance(*x, home, false) > SCOUT_RANGE;
+            let is_defended = intel::get(&x.to_string()).map_or(false, |x| {
+                x.hostile_structures > 0 && x.controller.map_or(false, |x| x.owner.is_some())
+            });
+            (
+                is_defended,
+                is_out_of_range,
+                intel::get_last_seen(&x.to_string()),
+            )
+        })
+}
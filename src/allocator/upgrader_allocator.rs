@@ -0,0 +1,148 @@
+use log::*;
+use screeps::{find, prelude::*, Part, RawObjectId, ResourceType, StructureContainer};
+
+use crate::creeps::{self, Creep, Role};
+use crate::economy;
+use crate::links;
+
+/// Controllers accept at most 15 energy per tick at RCL 8.
+const MAX_WORK_PARTS: u32 = 15;
+const MAX_UPGRADERS: u32 = 4;
+/// Each this much energy in the controller container adds an upgrader.
+const CONTAINER_ENERGY_PER_UPGRADER: u32 = 1000;
+/// Upgraders work within this range of the controller.
+pub const CONTROLLER_RANGE: u32 = 2;
+
+pub fn get_description(capacity: u32) -> Option<Vec<Part>> {
+    if get_target_room().is_none() {
+        return None;
+    }
+
+    Some(get_body(capacity))
+}
+
+pub fn get_body(capacity: u32) -> Vec<Part> {
+    let mut body = vec![Part::Carry, Part::Move];
+    let base_body_cost = body.iter().map(|p| p.cost()).sum::<u32>();
+    let part_set_cost = 2 * Part::Work.cost() + Part::Move.cost();
+    let part_sets = std::cmp::min(
+        capacity.saturating_sub(base_body_cost) / part_set_cost,
+        MAX_WORK_PARTS / 2,
+    );
+    for _ in 0..part_sets {
+        body.push(Part::Work);
+        body.push(Part::Work);
+        body.push(Part::Move);
+    }
+    body
+}
+
+pub fn allocate_creeps(creeps: Vec<Creep>) {
+    for creep in creeps {
+        allocate_creep(creep);
+    }
+}
+
+fn allocate_creep(creep: Creep) {
+    let controller = match creep.get_output::<screeps::StructureController>() {
+        Some(controller) => controller,
+        None => match get_target_room().and_then(|x| x.controller()) {
+            Some(controller) => {
+                creep.set_output(controller.untyped_id());
+                controller
+            }
+            None => return,
+        },
+    };
+
+    if let Some(input_id) = get_energy_source(&controller.room()) {
+        creep.set_input(input_id);
+    }
+}
+
+/// The container beside the controller, once built.
+pub fn get_controller_container(room: &screeps::Room) -> Option<StructureContainer> {
+    let controller = room.controller()?;
+    room.find(find::STRUCTURES)
+        .into_iter()
+        .filter_map(|x| match x {
+            screeps::Structure::Container(container) => Some(container),
+            _ => None,
+        })
+        .find(|x| controller.pos().get_range_to(x) <= CONTROLLER_RANGE)
+}
+
+/// Where upgraders fill up: the controller link or container while they
+/// have energy, then storage.
+fn get_energy_source(room: &screeps::Room) -> Option<RawObjectId> {
+    if let Some(link) = links::get_links(room).controller {
+        if link.store_of(ResourceType::Energy) > 0 {
+            return Some(link.untyped_id());
+        }
+    }
+    let container = get_controller_container(room);
+    if let Some(container) = &container {
+        if container.store_of(ResourceType::Energy) > 0 {
+            return Some(container.untyped_id());
+        }
+    }
+    if let Some(storage) = room.storage() {
+        if storage.store_of(ResourceType::Energy) > 0 {
+            return Some(storage.untyped_id());
+        }
+    }
+    return container.map(|x| x.untyped_id());
+}
+
+/// The owned room furthest below its upgrader target.
+fn get_target_room() -> Option<screeps::Room> {
+    let upgraders: Vec<Creep> = creeps::all()
+        .into_iter()
+        .filter(|x| x.role == Role::Upgrader)
+        .collect();
+
+    let mut result = None;
+    let mut max_missing = 0;
+    for room in screeps::game::rooms::values() {
+        let controller = match room.controller() {
+            Some(controller) if controller.my() => controller,
+            _ => continue,
+        };
+        let count = upgraders
+            .iter()
+            .filter(|x| x.get_stored_id("output") == Some(controller.untyped_id()))
+            .count() as u32;
+        let target_count = get_target_count(&room);
+        if target_count > count && target_count - count > max_missing {
+            max_missing = target_count - count;
+            result = Some(room);
+        }
+    }
+    if let Some(room) = &result {
+        debug!("{} needs {} more upgraders", room.name(), max_missing);
+    }
+    return result;
+}
+
+/// Upgraders are only worth it with somewhere to feed them from; beyond
+/// the first, more come with more spare energy.
+fn get_target_count(room: &screeps::Room) -> u32 {
+    let controller = match room.controller() {
+        Some(controller) => controller,
+        None => return 0,
+    };
+    if controller.level() == 8 {
+        return 1;
+    }
+    if room.storage().is_some() {
+        return economy::get_upgrader_count(room);
+    }
+    if let Some(container) = get_controller_container(room) {
+        let energy = container.store_of(ResourceType::Energy);
+        return (1 + energy / CONTAINER_ENERGY_PER_UPGRADER).min(MAX_UPGRADERS);
+    }
+    if links::get_links(room).controller.is_some() {
+        return 1;
+    }
+    return 0;
+}
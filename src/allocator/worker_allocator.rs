@@ -1,32 +1,41 @@
 use log::*;
 use screeps::{
-    find, prelude::*, ConstructionSite, Part, Position, RawObjectId, StructureController,
+    find, prelude::*, Attackable, ConstructionSite, Part, Position, RawObjectId, ResourceType,
+    Structure, StructureController, StructureStorage,
 };
 
-use crate::creeps::{Creep, Role};
+use crate::creeps::{self, Creep, Role};
+use crate::economy;
 
 pub fn get_description(capacity: u32) -> Option<Vec<Part>> {
     if !can_allocate_more() {
         return None;
     }
 
+    Some(get_body(capacity))
+}
+
+pub fn get_body(capacity: u32) -> Vec<Part> {
     let part_set = [Part::Move, Part::Carry, Part::Work];
     let part_set_cost: u32 = part_set.iter().map(|part| part.cost()).sum();
-    let number_of_part_sets = capacity / part_set_cost;
+    let number_of_part_sets = std::cmp::min(
+        capacity / part_set_cost,
+        super::MAX_BODY_PARTS / part_set.len() as u32,
+    );
     let mut result: Vec<Part> = Vec::new();
     for part in &part_set {
         for _ in 0..number_of_part_sets {
             result.push(*part);
         }
     }
-    let mut left_over_energy = capacity - part_set_cost;
+    let mut left_over_energy = capacity - number_of_part_sets * part_set_cost;
     for part in &part_set {
-        if part.cost() <= left_over_energy {
+        if part.cost() <= left_over_energy && (result.len() as u32) < super::MAX_BODY_PARTS {
             left_over_energy -= part.cost();
             result.push(*part);
         }
     }
-    Some(result)
+    result
 }
 
 pub fn allocate_creeps(creeps: Vec<Creep>) {
@@ -41,20 +50,38 @@ fn allocate_creep(creep: Creep) {
 }
 
 fn can_allocate_more() -> bool {
-    let worker_count = screeps::game::creeps::values()
+    let worker_count = creeps::all()
         .iter()
-        .map(|x| Creep::new(x.clone()))
         .filter(|x| x.role == Role::Worker)
         .count();
     return worker_count < 2;
 }
 
 fn allocate_input(creep: &Creep) {
+    if let Some(pickup_id) = super::pickup::find_pickup_target(creep) {
+        creep.set_input(pickup_id);
+        return;
+    }
+    if let Some(storage) = find_storage_with_energy(creep) {
+        creep.set_input(storage.untyped_id());
+        return;
+    }
     if let Some(spawn) = screeps::game::spawns::values().pop() {
         creep.set_input(spawn.untyped_id());
     }
 }
 
+/// Storage in the creep's room, or else any storage, that has energy.
+fn find_storage_with_energy(creep: &Creep) -> Option<StructureStorage> {
+    let mut storages: Vec<StructureStorage> = find_my_controllers()
+        .iter()
+        .filter_map(|x| x.room().storage())
+        .filter(|x| x.store_of(ResourceType::Energy) > 0)
+        .collect();
+    storages.sort_by_key(|x| x.room().name() != creep.room_name());
+    return storages.into_iter().next();
+}
+
 fn allocate_output(creep: &Creep) {
     if let Some(output_id) = allocate_output_id() {
         creep.set_output(output_id);
@@ -68,7 +95,44 @@ fn allocate_output_id() -> Option<RawObjectId> {
     if let Some(last_site) = screeps::game::construction_sites::values().pop() {
         return Some(last_site.untyped_id());
     }
-    return make_new_construction_site();
+    if let Some(site_id) = make_new_construction_site() {
+        return Some(site_id);
+    }
+    return find_wall_to_repair().map(|x| x.untyped_id());
+}
+
+/// The weakest wall or rampart below what its room can afford to spend.
+fn find_wall_to_repair() -> Option<Structure> {
+    let mut result: Option<(u32, Structure)> = None;
+    for controller in find_my_controllers() {
+        let room = controller.room();
+        let target_hits = economy::get_wall_target(&room);
+        for structure in room.find(find::STRUCTURES) {
+            let hits = match &structure {
+                Structure::Wall(wall) => wall.hits(),
+                Structure::Rampart(rampart) if rampart.my() => rampart.hits(),
+                _ => continue,
+            };
+            if hits >= target_hits {
+                continue;
+            }
+            if result
+                .as_ref()
+                .map_or(true, |(min_hits, _)| hits < *min_hits)
+            {
+                result = Some((hits, structure));
+            }
+        }
+    }
+    return result.map(|(_, structure)| structure);
+}
+
+/// Owned controllers whose rooms can afford new construction.
+fn find_controllers_to_build() -> Vec<StructureController> {
+    find_my_controllers()
+        .into_iter()
+        .filter(|x| economy::can_start_construction(&x.room()))
+        .collect()
 }
 
 fn find_controller_to_upgrade() -> Option<StructureController> {
@@ -102,12 +166,101 @@ fn make_new_construction_site() -> Option<RawObjectId> {
         info!("New extension {:?}", extension.pos());
         return Some(extension.untyped_id());
     }
+    if let Some(extractor) = make_new_extractor() {
+        info!("New extractor {:?}", extractor.pos());
+        return Some(extractor.untyped_id());
+    }
+    if let Some(container) = make_new_controller_container() {
+        info!("New controller container {:?}", container.pos());
+        return Some(container.untyped_id());
+    }
+
+    return None;
+}
+
+fn make_new_extractor() -> Option<ConstructionSite> {
+    const MINIMUM_EXTRACTOR_LEVEL: u32 = 6;
+
+    for controller in find_controllers_to_build() {
+        if controller.level() < MINIMUM_EXTRACTOR_LEVEL {
+            continue;
+        }
+        let room = controller.room();
+        for mineral in room.find(find::MINERALS) {
+            let has_extractor = room
+                .look_for_at(screeps::look::STRUCTURES, &mineral.pos())
+                .iter()
+                .any(|x| x.structure_type() == screeps::StructureType::Extractor);
+            let has_site = !room
+                .look_for_at(screeps::look::CONSTRUCTION_SITES, &mineral.pos())
+                .is_empty();
+            if has_extractor || has_site {
+                continue;
+            }
+
+            let return_code =
+                room.create_construction_site(&mineral.pos(), screeps::StructureType::Extractor);
+            if return_code == screeps::ReturnCode::Ok {
+                return room
+                    .look_for_at(screeps::look::CONSTRUCTION_SITES, &mineral.pos())
+                    .pop();
+            }
+        }
+    }
+
+    return None;
+}
+
+/// Places a container for upgraders on the path from the spawn to the
+/// controller.
+fn make_new_controller_container() -> Option<ConstructionSite> {
+    const MINIMUM_CONTAINER_LEVEL: u32 = 2;
+
+    for controller in find_controllers_to_build() {
+        if controller.level() < MINIMUM_CONTAINER_LEVEL {
+            continue;
+        }
+        let room = controller.room();
+        if super::upgrader_allocator::get_controller_container(&room).is_some() {
+            continue;
+        }
+        let has_site = room.find(find::MY_CONSTRUCTION_SITES).iter().any(|x| {
+            x.structure_type() == screeps::StructureType::Container
+                && controller.pos().get_range_to(x) <= super::upgrader_allocator::CONTROLLER_RANGE
+        });
+        if has_site {
+            continue;
+        }
+
+        let spawn = match find_extension_root(&controller) {
+            Some(spawn) => spawn,
+            None => continue,
+        };
+        let path = screeps::pathfinder::search(
+            &spawn,
+            &controller.pos(),
+            super::upgrader_allocator::CONTROLLER_RANGE,
+            screeps::pathfinder::SearchOptions::default(),
+        )
+        .load_local_path();
+        let position = match path.last() {
+            Some(position) => *position,
+            None => continue,
+        };
+        let return_code =
+            room.create_construction_site(&position, screeps::StructureType::Container);
+        if return_code == screeps::ReturnCode::Ok {
+            return room
+                .look_for_at(screeps::look::CONSTRUCTION_SITES, &position)
+                .pop();
+        }
+    }
 
     return None;
 }
 
 fn make_new_extension() -> Option<ConstructionSite> {
-    for controller in find_my_controllers() {
+    for controller in find_controllers_to_build() {
         if can_build_extension(&controller) {
             return place_extension_construction_site(&controller);
         }
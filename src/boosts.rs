@@ -0,0 +1,251 @@
+//! Boosting new creeps in labs.
+//!
+//! A spawn request can carry boosts for some of its body parts. They are
+//! only requested while the room has the compound in stock beyond what is
+//! already reserved. Once the creep spawns, each boost reserves a lab, which
+//! haulers fill with the compound and energy, in `Memory.rooms.<room>.boosts`.
+//! The creep visits its labs before starting on its role; a boost whose lab
+//! isn't ready in time is skipped.
+
+use crate::codec::{self, Decode, Decoder, Encode, Encoder};
+use crate::creeps::{Creep, Role, Task};
+use crate::labs;
+use crate::resources;
+use crate::rooms;
+use log::*;
+use screeps::{prelude::*, Part, RawObjectId, ResourceType, RoomName, StructureLab};
+
+/// LAB_BOOST_MINERAL
+pub const BOOST_AMOUNT_PER_PART: u32 = 30;
+/// LAB_BOOST_ENERGY
+pub const BOOST_ENERGY_PER_PART: u32 = 20;
+/// A new creep waits this long for its labs to be filled.
+const MAX_WAIT_TIME: u32 = 100;
+/// CREEP_LIFE_TIME
+const CREEP_LIFE_TIME: u32 = 1500;
+
+/// The boost each role wants for a body part.
+const ROLE_BOOSTS: [(Role, Part, ResourceType); 1] = [(
+    Role::Upgrader,
+    Part::Work,
+    ResourceType::CatalyzedGhodiumAcid,
+)];
+
+/// A lab set aside to boost some parts of one creep.
+#[derive(Debug, Clone)]
+pub struct Reservation {
+    pub creep: String,
+    pub lab: RawObjectId,
+    pub compound: ResourceType,
+    pub parts: u32,
+}
+
+impl Encode for Reservation {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.creep.encode(encoder);
+        self.lab.encode(encoder);
+        self.compound.encode(encoder);
+        self.parts.encode(encoder);
+    }
+}
+
+impl Decode for Reservation {
+    fn decode(decoder: &mut Decoder) -> Option<Self> {
+        Some(Reservation {
+            creep: Decode::decode(decoder)?,
+            lab: Decode::decode(decoder)?,
+            compound: Decode::decode(decoder)?,
+            parts: Decode::decode(decoder)?,
+        })
+    }
+}
+
+/// Drops reservations for creeps that no longer exist.
+pub fn game_loop() {
+    for room in screeps::game::rooms::values() {
+        let room_name = room.name().to_string();
+        let reservations = get_reservations(&room_name);
+        if reservations.is_empty() {
+            continue;
+        }
+        let live: Vec<Reservation> = reservations
+            .iter()
+            .filter(|x| screeps::game::creeps::get(&x.creep).is_some())
+            .cloned()
+            .collect();
+        if live.len() != reservations.len() {
+            set_reservations(&room_name, &live);
+        }
+    }
+}
+
+/// Boosts for a new creep that the room has the compound and a lab for.
+pub fn get_boosts(role: &Role, room: &screeps::Room, body: &[Part]) -> Vec<(Part, ResourceType)> {
+    let mut result = vec![];
+    if labs::get_lab_groups(room).map_or(true, |x| x.outputs.is_empty()) {
+        return result;
+    }
+    let stock = labs::get_stock(room);
+    let reservations = get_reservations(&room.name().to_string());
+    for (_, part, compound) in ROLE_BOOSTS.iter().filter(|(x, _, _)| x == role) {
+        let parts = body.iter().filter(|x| *x == part).count() as u32;
+        let in_stock = stock
+            .iter()
+            .find(|(x, _)| x == compound)
+            .map_or(0, |(_, amount)| *amount);
+        let reserved: u32 = reservations
+            .iter()
+            .filter(|x| x.compound == *compound)
+            .map(|x| x.parts * BOOST_AMOUNT_PER_PART)
+            .sum();
+        if parts > 0 && in_stock >= reserved + parts * BOOST_AMOUNT_PER_PART {
+            result.push((*part, *compound));
+        }
+    }
+    return result;
+}
+
+/// Sets aside labs for a creep that was just spawned with boosts.
+pub fn reserve(room_name: &str, creep_name: &str, boosts: &[(Part, ResourceType)], body: &[Part]) {
+    let room = match RoomName::new(room_name)
+        .ok()
+        .and_then(|x| screeps::game::rooms::get(x))
+    {
+        Some(room) => room,
+        None => return,
+    };
+    let outputs = match labs::get_lab_groups(&room) {
+        Some(lab_groups) => lab_groups.outputs,
+        None => return,
+    };
+    let mut reservations = get_reservations(room_name);
+    for (part, compound) in boosts {
+        // Share a lab with the same compound, otherwise take a free one.
+        let lab = outputs
+            .iter()
+            .find(|x| {
+                reservations
+                    .iter()
+                    .any(|r| r.lab == x.untyped_id() && r.compound == *compound)
+            })
+            .or_else(|| {
+                outputs
+                    .iter()
+                    .find(|x| !reservations.iter().any(|r| r.lab == x.untyped_id()))
+            });
+        let lab = match lab {
+            Some(lab) => lab,
+            None => {
+                warn!(
+                    "{}: no lab free to boost {} with {}",
+                    room_name,
+                    creep_name,
+                    resources::to_string(*compound)
+                );
+                continue;
+            }
+        };
+        reservations.push(Reservation {
+            creep: creep_name.to_string(),
+            lab: lab.untyped_id(),
+            compound: *compound,
+            parts: body.iter().filter(|x| *x == part).count() as u32,
+        });
+    }
+    set_reservations(room_name, &reservations);
+}
+
+/// Sends a newly spawned creep to its next boost. Returns true while the
+/// creep is busy being boosted. A reservation is only dropped once the
+/// creep's parts carry the boost, so haulers leave the lab alone while the
+/// creep walks over, and a boost that fails is retried until it times out.
+pub fn allocate_boosts(creep: &Creep) -> bool {
+    let home = match creep.get_home() {
+        Some(home) => home,
+        None => return false,
+    };
+    let mut reservations = get_reservations(&home);
+    let index = match reservations.iter().position(|x| x.creep == creep.name()) {
+        Some(index) => index,
+        None => return false,
+    };
+    if creep.is_spawning() || !creep.get_tasks().is_empty() {
+        return true;
+    }
+
+    let reservation = reservations[index].clone();
+    if creep.count_boosted_parts(reservation.compound) > 0 {
+        reservations.remove(index);
+        set_reservations(&home, &reservations);
+        return true;
+    }
+    let lab = screeps::game::get_object_typed::<StructureLab>(reservation.lab.into())
+        .ok()
+        .flatten();
+    let is_ready = lab
+        .as_ref()
+        .map_or(false, |x| is_lab_ready(x, &reservation));
+    let age = CREEP_LIFE_TIME.saturating_sub(creep.ticks_to_live());
+    if is_ready {
+        creep.push_task(Task::Boost(reservation.lab));
+        return true;
+    }
+    if age < MAX_WAIT_TIME {
+        return true;
+    }
+    warn!(
+        "{}: skipping boost with {}, lab not ready",
+        creep.name(),
+        resources::to_string(reservation.compound)
+    );
+    reservations.remove(index);
+    set_reservations(&home, &reservations);
+    return true;
+}
+
+/// The compound and energy a reserved lab should hold, if it is reserved.
+pub fn get_lab_requirement(
+    room_name: &str,
+    lab_id: RawObjectId,
+) -> Option<(ResourceType, u32, u32)> {
+    let reservations: Vec<Reservation> = get_reservations(room_name)
+        .into_iter()
+        .filter(|x| x.lab == lab_id)
+        .collect();
+    let compound = reservations.first()?.compound;
+    let parts: u32 = reservations.iter().map(|x| x.parts).sum();
+    Some((
+        compound,
+        parts * BOOST_AMOUNT_PER_PART,
+        parts * BOOST_ENERGY_PER_PART,
+    ))
+}
+
+fn is_lab_ready(lab: &StructureLab, reservation: &Reservation) -> bool {
+    lab.store_of(reservation.compound) >= reservation.parts * BOOST_AMOUNT_PER_PART
+        && lab.store_of(ResourceType::Energy) >= reservation.parts * BOOST_ENERGY_PER_PART
+}
+
+pub fn get_reservations(room_name: &str) -> Vec<Reservation> {
+    let memory = match rooms::memory(room_name) {
+        Some(memory) => memory,
+        None => return vec![],
+    };
+    if let Ok(Some(reservations_string)) = memory.string("boosts") {
+        if let Some(reservations) = codec::decode(&reservations_string) {
+            return reservations;
+        }
+        warn!("{}: discarding unreadable boost reservations", room_name);
+    }
+    return vec![];
+}
+
+fn set_reservations(room_name: &str, reservations: &Vec<Reservation>) {
+    if let Some(memory) = rooms::memory(room_name) {
+        if reservations.is_empty() {
+            memory.del("boosts");
+        } else {
+            memory.set("boosts", codec::encode(reservations));
+        }
+    }
+}
@@ -0,0 +1,459 @@
+//! Compact encoding of bot state into strings.
+//!
+//! Values are packed into a bit stream which is stored 15 bits per UTF-16
+//! code unit. Code units are offset past the ASCII range so the result never
+//! contains characters that JSON would escape, and never reaches the
+//! surrogate range, so it can be kept in `Memory` as well as in segments.
+
+const BITS_PER_UNIT: u32 = 15;
+const UNIT_OFFSET: u32 = 0x100;
+/// Leads data written by `encode_versioned`. Data from before versioning
+/// starts with a collection length instead, which never gets this large.
+const VERSION_MARKER: u32 = 0xFFFFF;
+
+pub trait Encode {
+    fn encode(&self, encoder: &mut Encoder);
+}
+
+pub trait Decode: Sized {
+    fn decode(decoder: &mut Decoder) -> Option<Self>;
+}
+
+pub fn encode<T>(value: &T) -> String
+where
+    T: Encode + ?Sized,
+{
+    let mut encoder = Encoder::new();
+    value.encode(&mut encoder);
+    return encoder.finish();
+}
+
+pub fn decode<T: Decode>(data: &str) -> Option<T> {
+    let mut decoder = Decoder::new(data)?;
+    return T::decode(&mut decoder);
+}
+
+/// Encodes `value` behind a layout version, so readers can still make sense
+/// of stored data after the type gains fields.
+pub fn encode_versioned<T>(version: u32, value: &T) -> String
+where
+    T: Encode + ?Sized,
+{
+    let mut encoder = Encoder::new();
+    encoder.write_varint(VERSION_MARKER);
+    encoder.write_varint(version);
+    value.encode(&mut encoder);
+    return encoder.finish();
+}
+
+/// Returns the layout version of `data` and a decoder positioned at the
+/// value. Data written before versioning is reported as version 0.
+pub fn decode_versioned(data: &str) -> Option<(u32, Decoder)> {
+    let mut decoder = Decoder::new(data)?;
+    if decoder.read_varint() == Some(VERSION_MARKER) {
+        return Some((decoder.read_varint()?, decoder));
+    }
+    return Some((0, Decoder::new(data)?));
+}
+
+pub struct Encoder {
+    output: String,
+    pending: u32,
+    pending_bits: u32,
+}
+
+impl Encoder {
+    pub fn new() -> Encoder {
+        Encoder {
+            output: String::new(),
+            pending: 0,
+            pending_bits: 0,
+        }
+    }
+
+    pub fn write_bits(&mut self, value: u32, count: u32) {
+        assert!(count <= 32);
+        for bit in (0..count).rev() {
+            self.pending = (self.pending << 1) | ((value >> bit) & 1);
+            self.pending_bits += 1;
+            if self.pending_bits == BITS_PER_UNIT {
+                self.push_unit();
+            }
+        }
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_bits(value as u32, 1);
+    }
+
+    /// Writes an unsigned integer in 7 bit groups, so small values stay small.
+    pub fn write_varint(&mut self, mut value: u32) {
+        loop {
+            let group = value & 0x7f;
+            value >>= 7;
+            self.write_bool(value != 0);
+            self.write_bits(group, 7);
+            if value == 0 {
+                return;
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> String {
+        if self.pending_bits > 0 {
+            self.pending <<= BITS_PER_UNIT - self.pending_bits;
+            self.push_unit();
+        }
+        return self.output;
+    }
+
+    fn push_unit(&mut self) {
+        let unit = std::char::from_u32(self.pending + UNIT_OFFSET)
+            .expect("expected 15 bit code units to stay below the surrogate range");
+        self.output.push(unit);
+        self.pending = 0;
+        self.pending_bits = 0;
+    }
+}
+
+pub struct Decoder {
+    units: Vec<u32>,
+    position: usize,
+}
+
+impl Decoder {
+    pub fn new(data: &str) -> Option<Decoder> {
+        let mut units = Vec::with_capacity(data.len());
+        for unit in data.chars() {
+            let value = (unit as u32).checked_sub(UNIT_OFFSET)?;
+            if value >= 1 << BITS_PER_UNIT {
+                return None;
+            }
+            units.push(value);
+        }
+        Some(Decoder {
+            units: units,
+            position: 0,
+        })
+    }
+
+    pub fn read_bits(&mut self, count: u32) -> Option<u32> {
+        assert!(count <= 32);
+        let mut result = 0;
+        for _ in 0..count {
+            let unit = self.units.get(self.position / BITS_PER_UNIT as usize)?;
+            let shift = BITS_PER_UNIT - 1 - (self.position % BITS_PER_UNIT as usize) as u32;
+            result = (result << 1) | ((unit >> shift) & 1);
+            self.position += 1;
+        }
+        return Some(result);
+    }
+
+    pub fn read_bool(&mut self) -> Option<bool> {
+        Some(self.read_bits(1)? == 1)
+    }
+
+    pub fn read_varint(&mut self) -> Option<u32> {
+        let mut result: u32 = 0;
+        let mut shift = 0;
+        loop {
+            let more = self.read_bool()?;
+            let group = self.read_bits(7)?;
+            if shift >= 32 {
+                return None;
+            }
+            result |= group << shift;
+            shift += 7;
+            if !more {
+                return Some(result);
+            }
+        }
+    }
+}
+
+impl Encode for u32 {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_varint(*self);
+    }
+}
+
+impl Decode for u32 {
+    fn decode(decoder: &mut Decoder) -> Option<Self> {
+        decoder.read_varint()
+    }
+}
+
+impl Encode for bool {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_bool(*self);
+    }
+}
+
+impl Decode for bool {
+    fn decode(decoder: &mut Decoder) -> Option<Self> {
+        decoder.read_bool()
+    }
+}
+
+impl Encode for str {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_varint(self.chars().count() as u32);
+        for c in self.chars() {
+            encoder.write_varint(c as u32);
+        }
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.as_str().encode(encoder);
+    }
+}
+
+impl Decode for String {
+    fn decode(decoder: &mut Decoder) -> Option<Self> {
+        let length = decoder.read_varint()?;
+        let mut result = String::new();
+        for _ in 0..length {
+            result.push(std::char::from_u32(decoder.read_varint()?)?);
+        }
+        return Some(result);
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_bool(self.is_some());
+        if let Some(value) = self {
+            value.encode(encoder);
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(decoder: &mut Decoder) -> Option<Self> {
+        if decoder.read_bool()? {
+            return Some(Some(T::decode(decoder)?));
+        }
+        return Some(None);
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_varint(self.len() as u32);
+        for value in self {
+            value.encode(encoder);
+        }
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(decoder: &mut Decoder) -> Option<Self> {
+        let length = decoder.read_varint()?;
+        let mut result = Vec::new();
+        for _ in 0..length {
+            result.push(T::decode(decoder)?);
+        }
+        return Some(result);
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.0.encode(encoder);
+        self.1.encode(encoder);
+    }
+}
+
+impl<A: Decode, B: Decode> Decode for (A, B) {
+    fn decode(decoder: &mut Decoder) -> Option<Self> {
+        Some((A::decode(decoder)?, B::decode(decoder)?))
+    }
+}
+
+impl Encode for screeps::RawObjectId {
+    fn encode(&self, encoder: &mut Encoder) {
+        // Ids are hex strings of at most 24 digits; store each digit in 4 bits.
+        let digits: Vec<u32> = self
+            .to_string()
+            .chars()
+            .filter_map(|x| x.to_digit(16))
+            .collect();
+        encoder.write_bits(digits.len() as u32, 5);
+        for digit in digits {
+            encoder.write_bits(digit, 4);
+        }
+    }
+}
+
+impl Decode for screeps::RawObjectId {
+    fn decode(decoder: &mut Decoder) -> Option<Self> {
+        let length = decoder.read_bits(5)?;
+        let mut id_string = String::new();
+        for _ in 0..length {
+            id_string.push(std::char::from_digit(decoder.read_bits(4)?, 16)?);
+        }
+        return id_string.parse().ok();
+    }
+}
+
+impl Encode for screeps::Position {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_bits(self.packed_repr() as u32, 32);
+    }
+}
+
+impl Decode for screeps::Position {
+    fn decode(decoder: &mut Decoder) -> Option<Self> {
+        Some(screeps::Position::from_packed(decoder.read_bits(32)? as i32))
+    }
+}
+
+const PARTS: [screeps::Part; 8] = [
+    screeps::Part::Move,
+    screeps::Part::Work,
+    screeps::Part::Carry,
+    screeps::Part::Attack,
+    screeps::Part::RangedAttack,
+    screeps::Part::Tough,
+    screeps::Part::Heal,
+    screeps::Part::Claim,
+];
+
+impl Encode for screeps::Part {
+    fn encode(&self, encoder: &mut Encoder) {
+        let index = PARTS
+            .iter()
+            .position(|x| x == self)
+            .expect("expected every body part to have a code");
+        encoder.write_bits(index as u32, 3);
+    }
+}
+
+impl Decode for screeps::Part {
+    fn decode(decoder: &mut Decoder) -> Option<Self> {
+        PARTS.get(decoder.read_bits(3)? as usize).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use screeps::{Part, Position, RawObjectId, RoomName};
+
+    fn round_trip<T: Encode + Decode>(value: &T) -> Option<T> {
+        decode(&encode(value))
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for value in [0, 1, 127, 128, 300, 1 << 20, u32::MAX].iter() {
+            assert_eq!(round_trip(value), Some(*value));
+        }
+    }
+
+    #[test]
+    fn bits_round_trip_across_units() {
+        let mut encoder = Encoder::new();
+        for count in 1..=32 {
+            encoder.write_bits(1 << (count - 1), count);
+        }
+        let data = encoder.finish();
+        let mut decoder = Decoder::new(&data).unwrap();
+        for count in 1..=32 {
+            assert_eq!(decoder.read_bits(count), Some(1 << (count - 1)));
+        }
+    }
+
+    #[test]
+    fn encoded_data_avoids_ascii_and_surrogates() {
+        let data = encode(&vec![u32::MAX; 10]);
+        assert!(data
+            .chars()
+            .all(|x| x as u32 >= UNIT_OFFSET && (x as u32) < 0xD800));
+    }
+
+    #[test]
+    fn collections_round_trip() {
+        let value = vec![
+            (Some("W1N1".to_string()), true),
+            (None, false),
+            (Some("ünïcødé".to_string()), true),
+        ];
+        assert_eq!(round_trip(&value), Some(value));
+        assert_eq!(round_trip(&Vec::<u32>::new()), Some(vec![]));
+    }
+
+    #[test]
+    fn object_ids_round_trip() {
+        for id_string in ["5bbcac4c9099fc012e6362f3", "1f2e3d4c5b6a798"].iter() {
+            let id: RawObjectId = id_string.parse().unwrap();
+            assert_eq!(round_trip(&id), Some(id));
+        }
+    }
+
+    #[test]
+    fn positions_round_trip() {
+        for (x, y, room_name) in [(0, 0, "W0N0"), (25, 25, "E12S34"), (49, 49, "W60N60")].iter() {
+            let position = Position::new(*x, *y, RoomName::new(room_name).unwrap());
+            assert_eq!(round_trip(&position), Some(position));
+        }
+    }
+
+    #[test]
+    fn parts_round_trip() {
+        let parts = PARTS.to_vec();
+        assert_eq!(round_trip(&parts), Some(parts));
+        let body = vec![Part::Work, Part::Work, Part::Carry, Part::Move];
+        assert_eq!(round_trip(&body), Some(body));
+    }
+
+    #[test]
+    fn versioned_data_round_trips() {
+        let data = encode_versioned(3, &vec![1, 2, 3]);
+        let (version, mut decoder) = decode_versioned(&data).unwrap();
+        assert_eq!(version, 3);
+        assert_eq!(Vec::<u32>::decode(&mut decoder), Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn unversioned_data_is_version_0() {
+        let data = encode(&vec![1, 2, 3]);
+        let (version, mut decoder) = decode_versioned(&data).unwrap();
+        assert_eq!(version, 0);
+        assert_eq!(Vec::<u32>::decode(&mut decoder), Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn truncated_data_is_none() {
+        let data = encode(&"hello world".to_string());
+        let truncated: String = data.chars().take(data.chars().count() - 1).collect();
+        assert_eq!(decode::<String>(&truncated), None);
+        assert_eq!(decode::<u32>(""), None);
+
+        let id: RawObjectId = "5bbcac4c9099fc012e6362f3".parse().unwrap();
+        let data = encode(&id);
+        let truncated: String = data.chars().take(data.chars().count() / 2).collect();
+        assert_eq!(decode::<RawObjectId>(&truncated), None);
+    }
+
+    #[test]
+    fn bad_data_is_none() {
+        assert_eq!(decode::<u32>("abc"), None);
+        assert_eq!(decode::<u32>("\u{9000}"), None);
+
+        // A varint that never ends.
+        let mut encoder = Encoder::new();
+        for _ in 0..6 {
+            encoder.write_bits(0xFF, 8);
+        }
+        assert_eq!(decode::<u32>(&encoder.finish()), None);
+
+        // A string of a character that doesn't exist.
+        let mut encoder = Encoder::new();
+        encoder.write_varint(1);
+        encoder.write_varint(0xD800);
+        assert_eq!(decode::<String>(&encoder.finish()), None);
+    }
+}
@@ -0,0 +1,224 @@
+//! Commands callable from the Screeps console, e.g. `bot.spawn('W1N1', 'worker')`.
+
+use crate::creeps::{self, Role};
+use crate::economy;
+use crate::expansion;
+use crate::logging;
+use crate::power_banks;
+use crate::spawn;
+use screeps::find;
+use std::str::FromStr;
+
+struct Command {
+    name: &'static str,
+    usage: &'static str,
+    description: &'static str,
+    run: fn(&[String]) -> Result<String, String>,
+}
+
+const COMMANDS: [Command; 6] = [
+    Command {
+        name: "help",
+        usage: "help()",
+        description: "List the available commands.",
+        run: help,
+    },
+    Command {
+        name: "spawn",
+        usage: "spawn(room, role)",
+        description: "Queue a creep of the given role in a room.",
+        run: spawn,
+    },
+    Command {
+        name: "setRole",
+        usage: "setRole(creep, role)",
+        description: "Reassign a live creep, including ones not spawned by the bot.",
+        run: set_role,
+    },
+    Command {
+        name: "setLog",
+        usage: "setLog(level, [target])",
+        description: "Set the log level, globally or for a module such as 'crate::spawn'.",
+        run: set_log,
+    },
+    Command {
+        name: "report",
+        usage: "report(room)",
+        description: "Summarize the state of a room.",
+        run: report,
+    },
+    Command {
+        name: "replan",
+        usage: "replan(room)",
+        description: "Remove the room's construction sites so they are placed again.",
+        run: replan,
+    },
+];
+
+pub fn command_names() -> Vec<String> {
+    COMMANDS.iter().map(|x| x.name.to_string()).collect()
+}
+
+/// Entry point for the console. Errors are returned as text rather than
+/// thrown so a typo doesn't reset the VM.
+pub fn dispatch(name: String, args: Vec<String>) -> String {
+    let command = match COMMANDS.iter().find(|x| x.name == name) {
+        Some(command) => command,
+        None => return format!("error: unknown command '{}', try bot.help()", name),
+    };
+    return match (command.run)(&args) {
+        Ok(result) => result,
+        Err(message) => format!("error: {}\nusage: bot.{}", message, command.usage),
+    };
+}
+
+fn get_arg<'a>(args: &'a [String], index: usize, name: &str) -> Result<&'a str, String> {
+    match args.get(index) {
+        Some(arg) => Ok(arg.as_str()),
+        None => Err(format!("missing argument '{}'", name)),
+    }
+}
+
+fn get_room(args: &[String], index: usize) -> Result<screeps::Room, String> {
+    let room_name_string = get_arg(args, index, "room")?;
+    let room_name = screeps::RoomName::new(room_name_string)
+        .map_err(|_| format!("invalid room name '{}'", room_name_string))?;
+    match screeps::game::rooms::get(room_name) {
+        Some(room) => Ok(room),
+        None => Err(format!("no vision of room '{}'", room_name_string)),
+    }
+}
+
+fn help(_args: &[String]) -> Result<String, String> {
+    let lines: Vec<String> = COMMANDS
+        .iter()
+        .map(|x| format!("bot.{}: {}", x.usage, x.description))
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+fn spawn(args: &[String]) -> Result<String, String> {
+    let room = get_room(args, 0)?;
+    let role = Role::from_str(get_arg(args, 1, "role")?).map_err(|x| x.to_string())?;
+    let room_name = room.name().to_string();
+    spawn::queue_request(&room_name, spawn::SpawnRequest::new(role.clone()));
+    Ok(format!(
+        "queued {} in {} ({} waiting)",
+        role.to_string(),
+        room_name,
+        spawn::get_queue(&room_name).len()
+    ))
+}
+
+fn set_role(args: &[String]) -> Result<String, String> {
+    let creep_name = get_arg(args, 0, "creep")?;
+    let role = Role::from_str(get_arg(args, 1, "role")?).map_err(|x| x.to_string())?;
+    let creep = match screeps::game::creeps::get(creep_name) {
+        Some(creep) => creep,
+        None => return Err(format!("no creep named '{}'", creep_name)),
+    };
+    creeps::set_role(&creep, &role);
+    Ok(format!("{} is now a {}", creep_name, role.to_string()))
+}
+
+fn set_log(args: &[String]) -> Result<String, String> {
+    let level_string = get_arg(args, 0, "level")?;
+    let level = log::LevelFilter::from_str(level_string)
+        .map_err(|_| format!("invalid log level '{}'", level_string))?;
+    let target = args.get(1).map(|x| x.as_str());
+    logging::set_level(target, level);
+    Ok(format!(
+        "log level for {} set to {}",
+        target.unwrap_or("all targets"),
+        level
+    ))
+}
+
+fn report(args: &[String]) -> Result<String, String> {
+    let room = get_room(args, 0)?;
+    let room_name = room.name().to_string();
+    let mut lines = vec![];
+
+    match room.controller() {
+        Some(controller) => lines.push(format!(
+            "{}: RCL {} ({}/{}), downgrade in {}",
+            room_name,
+            controller.level(),
+            controller.progress().unwrap_or(0),
+            controller.progress_total().unwrap_or(0),
+            controller.ticks_to_downgrade()
+        )),
+        None => lines.push(format!("{}: no controller", room_name)),
+    }
+    lines.push(format!(
+        "energy: {}/{} ({:?})",
+        room.energy_available(),
+        room.energy_capacity_available(),
+        economy::get_energy_level(&room)
+    ));
+
+    let mut role_counts: Vec<(&'static str, u32)> = vec![];
+    for creep in creeps::all() {
+        if creep.room_name() != room.name() {
+            continue;
+        }
+        let role = creep.role.to_string();
+        match role_counts.iter_mut().find(|(x, _)| *x == role) {
+            Some((_, count)) => *count += 1,
+            None => role_counts.push((role, 1)),
+        }
+    }
+    let role_strings: Vec<String> = role_counts
+        .iter()
+        .map(|(role, count)| format!("{} {}", role, count))
+        .collect();
+    lines.push(format!("creeps: {}", role_strings.join(", ")));
+
+    let queue_strings: Vec<&str> = spawn::get_queue(&room_name)
+        .iter()
+        .map(|x| x.role.to_string())
+        .collect();
+    lines.push(format!("spawn queue: {}", queue_strings.join(", ")));
+    if let Some(spawn_status) = spawn::get_spawn_status(&room_name) {
+        lines.push(format!("last spawn failure: {}", spawn_status));
+    }
+    lines.push(format!(
+        "construction sites: {}",
+        room.find(find::MY_CONSTRUCTION_SITES).len()
+    ));
+    if let Some(expansion) = expansion::get_expansion() {
+        if expansion.parent == room_name || expansion.target == room_name {
+            lines.push(format!(
+                "expansion: {} -> {} {:?} since {}",
+                expansion.parent, expansion.target, expansion.status, expansion.since
+            ));
+        }
+    }
+    if let Some(operation) = power_banks::get_operation() {
+        if operation.parent == room_name || operation.room == room_name {
+            lines.push(format!(
+                "power bank: {} -> {} {:?} since {}, {} power",
+                operation.parent,
+                operation.room,
+                operation.status,
+                operation.since,
+                operation.power
+            ));
+        }
+    }
+
+    Ok(lines.join("\n"))
+}
+
+fn replan(args: &[String]) -> Result<String, String> {
+    let room = get_room(args, 0)?;
+    let sites = room.find(find::MY_CONSTRUCTION_SITES);
+    for site in &sites {
+        site.remove();
+    }
+    Ok(format!(
+        "removed {} construction sites in {}",
+        sites.len(),
+        room.name()
+    ))
+}
@@ -0,0 +1,68 @@
+//! Records Rust panics before the module aborts.
+//!
+//! With `panic = "abort"` a panic surfaces in JS as an opaque "unreachable"
+//! trap, so the hook reports the message and location itself and keeps a
+//! log in `Memory.crashes` to make crash loops visible after the fact.
+
+use stdweb::js;
+
+const MAX_LOGGED_CRASHES: usize = 10;
+
+pub fn setup_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let message = if let Some(message) = info.payload().downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = info.payload().downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown panic".to_string()
+        };
+        let location = match info.location() {
+            Some(location) => format!(
+                "{}:{}:{}",
+                location.file(),
+                location.line(),
+                location.column()
+            ),
+            None => "unknown location".to_string(),
+        };
+        let time = screeps::game::time();
+        let report = format!("[{}] panicked at {}: {}", time, location, message);
+
+        js! {
+            // console_error function provided by 'screeps-game-api'
+            console_error(@{report.clone()});
+        }
+        record_crash(time, &report);
+    }));
+}
+
+fn record_crash(time: u32, report: &str) {
+    let crashes = match screeps::memory::root().dict_or_create("crashes") {
+        Ok(crashes) => crashes,
+        Err(_) => return,
+    };
+
+    // Every panic aborts the module and resets the VM.
+    let count = crashes.i32("count").ok().flatten().unwrap_or(0);
+    crashes.set("count", count + 1);
+
+    // Panics on consecutive ticks mean the bot is stuck in a crash loop.
+    let last_time = crashes.i32("last_time").ok().flatten().unwrap_or(0);
+    let streak = crashes.i32("streak").ok().flatten().unwrap_or(0);
+    if time as i32 - last_time <= 1 {
+        crashes.set("streak", streak + 1);
+    } else {
+        crashes.set("streak", 1);
+    }
+    crashes.set("last_time", time);
+
+    if let Ok(log) = crashes.dict_or_create("log") {
+        log.set(&time.to_string(), report);
+        let mut times: Vec<u32> = log.keys().iter().filter_map(|x| x.parse().ok()).collect();
+        times.sort();
+        for old_time in times.iter().rev().skip(MAX_LOGGED_CRASHES) {
+            log.del(&old_time.to_string());
+        }
+    }
+}
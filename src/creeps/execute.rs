@@ -1,15 +1,19 @@
 use log::*;
 use screeps::{
-    prelude::*, ConstructionSite, ResourceType, ReturnCode, Source, Structure, StructureController,
+    look, prelude::*, ConstructionSite, Mineral, Resource, ResourceType, ReturnCode, Ruin, Source,
+    Structure, StructureController, Tombstone,
 };
 
-use super::Creep;
+use super::{task, Creep};
 
 pub fn execute(creep: &Creep) {
     debug!("running {}", creep.creep.name());
     if creep.creep.spawning() {
         return;
     }
+    if task::execute(creep) {
+        return;
+    }
 
     update_mode(creep);
     execute_mode(creep);
@@ -34,7 +38,7 @@ fn execute_mode(creep: &Creep) {
         super::Mode::Output => execute_output_mode(creep),
         super::Mode::Idle => ReturnCode::Ok,
     };
-    if return_code == ReturnCode::NotInRange {
+    if return_code == ReturnCode::NotInRange || return_code == ReturnCode::Full {
         debug!(
             "Failed '{:?}' to '{:?}': {:?}",
             creep.get_mode(),
@@ -54,14 +58,35 @@ fn execute_mode(creep: &Creep) {
 fn execute_input_mode(creep: &Creep) -> ReturnCode {
     if creep.get_target::<Source>().is_some() {
         debug!("harvest");
+        pickup_overflow(creep);
         return harvest(creep);
     }
+    if creep.get_target::<Mineral>().is_some() {
+        debug!("harvest_mineral");
+        return harvest_mineral(creep);
+    }
+    if creep.get_target::<Resource>().is_some() {
+        debug!("pickup");
+        return pickup(creep);
+    }
+    if creep.get_target::<Tombstone>().is_some() || creep.get_target::<Ruin>().is_some() {
+        debug!("withdraw_from_remains");
+        return withdraw_from_remains(creep);
+    }
     debug!("transfer_from");
     return transfer_from(creep);
 }
 
 fn execute_output_mode(creep: &Creep) -> ReturnCode {
     if creep.get_target::<StructureController>().is_some() {
+        if creep.role == super::Role::Reserver {
+            debug!("reserve_controller");
+            return reserve_controller(creep);
+        }
+        if creep.role == super::Role::Claimer {
+            debug!("claim_controller");
+            return claim_controller(creep);
+        }
         debug!("upgrade_controller");
         return upgrade_controller(creep);
     }
@@ -69,6 +94,11 @@ fn execute_output_mode(creep: &Creep) -> ReturnCode {
         debug!("build");
         return build(creep);
     }
+    if let Some(Structure::Wall(_)) | Some(Structure::Rampart(_)) = creep.get_target::<Structure>()
+    {
+        debug!("repair");
+        return repair(creep);
+    }
     debug!("transfer_to");
     return transfer_to(creep);
 }
@@ -76,9 +106,12 @@ fn execute_output_mode(creep: &Creep) -> ReturnCode {
 fn transfer_to(creep: &Creep) -> ReturnCode {
     if let Some(target_structure) = creep.get_target::<Structure>() {
         if let Some(target_transferable) = target_structure.as_transferable() {
-            return creep
-                .creep
-                .transfer_all(target_transferable, ResourceType::Energy);
+            // Empty out one resource type per tick, starting with whatever
+            // the creep carries that isn't energy.
+            let mut resources = creep.creep.store_types();
+            resources.sort_by_key(|x| *x == ResourceType::Energy);
+            let resource = resources.first().cloned().unwrap_or(ResourceType::Energy);
+            return creep.creep.transfer_all(target_transferable, resource);
         }
     }
     return ReturnCode::InvalidTarget;
@@ -91,6 +124,28 @@ fn upgrade_controller(creep: &Creep) -> ReturnCode {
     return ReturnCode::InvalidTarget;
 }
 
+fn reserve_controller(creep: &Creep) -> ReturnCode {
+    if let Some(target_controller) = creep.get_target::<StructureController>() {
+        return creep.creep.reserve_controller(&target_controller);
+    }
+    return ReturnCode::InvalidTarget;
+}
+
+fn repair(creep: &Creep) -> ReturnCode {
+    return match creep.get_target::<Structure>() {
+        Some(Structure::Wall(wall)) => creep.creep.repair(&wall),
+        Some(Structure::Rampart(rampart)) => creep.creep.repair(&rampart),
+        _ => ReturnCode::InvalidTarget,
+    };
+}
+
+fn claim_controller(creep: &Creep) -> ReturnCode {
+    if let Some(target_controller) = creep.get_target::<StructureController>() {
+        return creep.creep.claim_controller(&target_controller);
+    }
+    return ReturnCode::InvalidTarget;
+}
+
 fn transfer_from(creep: &Creep) -> ReturnCode {
     assert!(creep.has_target());
 
@@ -98,12 +153,65 @@ fn transfer_from(creep: &Creep) -> ReturnCode {
         if let Some(target_withdrawable) = target_structure.as_withdrawable() {
             return creep
                 .creep
-                .withdraw_all(target_withdrawable, ResourceType::Energy);
+                .withdraw_all(target_withdrawable, creep.get_resource());
         }
     }
     return ReturnCode::InvalidTarget;
 }
 
+fn harvest_mineral(creep: &Creep) -> ReturnCode {
+    if let Some(mineral) = creep.get_target::<Mineral>() {
+        // Wait beside the mineral while the extractor cools down.
+        if creep.get_range_to(&mineral) <= 1 && get_extractor_cooldown(&mineral) > 0 {
+            return ReturnCode::Ok;
+        }
+        return creep.creep.harvest(&mineral);
+    }
+    return ReturnCode::InvalidTarget;
+}
+
+fn get_extractor_cooldown(mineral: &Mineral) -> u32 {
+    for structure in mineral.room().look_for_at(look::STRUCTURES, &mineral.pos()) {
+        if let Structure::Extractor(extractor) = structure {
+            return extractor.cooldown();
+        }
+    }
+    return 0;
+}
+
+fn pickup(creep: &Creep) -> ReturnCode {
+    if let Some(resource) = creep.get_target::<Resource>() {
+        return creep.creep.pickup(&resource);
+    }
+    return ReturnCode::InvalidTarget;
+}
+
+/// Recovers energy that spilled next to the creep, e.g. from a full
+/// container under a harvester.
+fn pickup_overflow(creep: &Creep) {
+    let room = match screeps::game::rooms::get(creep.room_name()) {
+        Some(room) => room,
+        None => return,
+    };
+    let overflow = room
+        .find(screeps::find::DROPPED_RESOURCES)
+        .into_iter()
+        .find(|x| x.resource_type() == ResourceType::Energy && creep.get_range_to(x) <= 1);
+    if let Some(resource) = overflow {
+        creep.creep.pickup(&resource);
+    }
+}
+
+fn withdraw_from_remains(creep: &Creep) -> ReturnCode {
+    if let Some(tombstone) = creep.get_target::<Tombstone>() {
+        return creep.creep.withdraw_all(&tombstone, ResourceType::Energy);
+    }
+    if let Some(ruin) = creep.get_target::<Ruin>() {
+        return creep.creep.withdraw_all(&ruin, ResourceType::Energy);
+    }
+    return ReturnCode::InvalidTarget;
+}
+
 fn harvest(creep: &Creep) -> ReturnCode {
     assert!(creep.has_target());
 
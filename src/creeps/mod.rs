@@ -1,10 +1,16 @@
+use crate::codec::{self, Decode, Decoder, Encode, Encoder};
+use crate::error::{Error, Result};
+use crate::resources;
 use log::*;
 use screeps::objects::HasPosition;
-use screeps::{prelude::*, Position, RawObjectId};
+use screeps::{prelude::*, Position, RawObjectId, ResourceType};
 use std::collections::HashSet;
 use std::str::FromStr;
 
 mod execute;
+pub mod task;
+
+pub use task::Task;
 
 #[derive(PartialEq, Debug)]
 pub enum Mode {
@@ -17,26 +23,53 @@ pub enum Mode {
 pub enum Role {
     Harvester,
     Worker,
+    MineralMiner,
+    Reserver,
+    RemoteMiner,
+    RemoteHauler,
+    Scout,
+    Claimer,
+    Pioneer,
+    Upgrader,
+    Hauler,
+    PowerAttacker,
+    PowerHealer,
+    PowerCarrier,
 }
 
-const ROLE_STRINGS: [(Role, &'static str); 2] =
-    [(Role::Harvester, "harvester"), (Role::Worker, "worker")];
+/// Each role's full name and the short code used in creep names.
+const ROLE_STRINGS: [(Role, &'static str, &'static str); 14] = [
+    (Role::Harvester, "harvester", "h"),
+    (Role::Worker, "worker", "w"),
+    (Role::MineralMiner, "mineral_miner", "m"),
+    (Role::Reserver, "reserver", "r"),
+    (Role::RemoteMiner, "remote_miner", "rm"),
+    (Role::RemoteHauler, "remote_hauler", "rh"),
+    (Role::Scout, "scout", "s"),
+    (Role::Claimer, "claimer", "c"),
+    (Role::Pioneer, "pioneer", "p"),
+    (Role::Upgrader, "upgrader", "u"),
+    (Role::Hauler, "hauler", "ha"),
+    (Role::PowerAttacker, "power_attacker", "pa"),
+    (Role::PowerHealer, "power_healer", "ph"),
+    (Role::PowerCarrier, "power_carrier", "pc"),
+];
 
 impl FromStr for Role {
-    type Err = String;
-    fn from_str(s: &str) -> Result<Self, String> {
-        for (role, role_string) in ROLE_STRINGS.iter() {
-            if s == *role_string {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        for (role, role_string, role_code) in ROLE_STRINGS.iter() {
+            if s == *role_string || s == *role_code {
                 return Ok(role.clone());
             }
         }
-        return Err("Invalid mode string:".to_owned() + &s.to_owned());
+        return Err(Error::UnknownRole(s.to_owned()));
     }
 }
 
 impl Role {
     pub fn to_string(&self) -> &'static str {
-        for (role, role_string) in ROLE_STRINGS.iter() {
+        for (role, role_string, _) in ROLE_STRINGS.iter() {
             if self == role {
                 return role_string;
             }
@@ -44,6 +77,33 @@ impl Role {
 
         panic!("Unable to convert role to string");
     }
+
+    pub fn code(&self) -> &'static str {
+        for (role, _, role_code) in ROLE_STRINGS.iter() {
+            if self == role {
+                return role_code;
+            }
+        }
+
+        panic!("Unable to convert role to code");
+    }
+}
+
+impl Encode for Role {
+    fn encode(&self, encoder: &mut Encoder) {
+        let index = ROLE_STRINGS
+            .iter()
+            .position(|(role, _, _)| role == self)
+            .expect("expected every role to have a string");
+        encoder.write_varint(index as u32);
+    }
+}
+
+impl Decode for Role {
+    fn decode(decoder: &mut Decoder) -> Option<Self> {
+        let (role, _, _) = ROLE_STRINGS.get(decoder.read_varint()? as usize)?;
+        Some(role.clone())
+    }
 }
 
 pub struct Creep {
@@ -52,15 +112,22 @@ pub struct Creep {
 }
 
 impl Creep {
-    pub fn new(creep: screeps::Creep) -> Creep {
-        let name = creep.name();
-        let name_prefix = name.split(":").next().unwrap();
-        let role = Role::from_str(name_prefix).unwrap();
+    /// The role is read from creep memory, falling back to the name prefix
+    /// for creeps that don't have one stored yet.
+    pub fn new(creep: screeps::Creep) -> Result<Creep> {
+        let role = match creep.memory().string("role") {
+            Ok(Some(role_string)) => Role::from_str(&role_string)?,
+            _ => {
+                let role = get_role_from_name(&creep.name())?;
+                creep.memory().set("role", role.to_string());
+                role
+            }
+        };
 
-        Creep {
+        Ok(Creep {
             creep: creep,
             role: role,
-        }
+        })
     }
     fn get_mode(&self) -> Mode {
         return match self.get_mode_string().as_str() {
@@ -137,7 +204,7 @@ impl Creep {
             std::line!(),
             target_id.to_string()
         );
-        self.creep.memory().set("input", target_id.to_string());
+        self.creep.memory().set("input", codec::encode(&target_id));
     }
 
     pub fn get_output<T>(&self) -> Option<T>
@@ -154,7 +221,7 @@ impl Creep {
             std::line!(),
             target_id.to_string()
         );
-        self.creep.memory().set("output", target_id.to_string());
+        self.creep.memory().set("output", codec::encode(&target_id));
     }
 
     fn get_stored_object<T>(&self, key: &str) -> Option<T>
@@ -167,8 +234,52 @@ impl Creep {
 
     pub fn get_stored_id(&self, key: &str) -> Option<RawObjectId> {
         let stored_target_id_string = self.creep.memory().string(key).ok()??;
-        let id = stored_target_id_string.parse().unwrap();
-        return Some(id);
+        // Fall back to the plain id strings written by older code.
+        let id = codec::decode(&stored_target_id_string)
+            .or_else(|| stored_target_id_string.parse().ok());
+        if id.is_none() {
+            let error = Error::MalformedMemory {
+                key: key.to_string(),
+                value: stored_target_id_string,
+            };
+            warn!("{}: discarding {}", self.creep.name(), error);
+            self.creep.memory().del(key);
+        }
+        return id;
+    }
+
+    /// The resource to withdraw in input mode, energy unless set.
+    pub fn get_resource(&self) -> ResourceType {
+        if let Ok(Some(resource_string)) = self.creep.memory().string("resource") {
+            if let Some(resource) = resources::from_str(&resource_string) {
+                return resource;
+            }
+        }
+        return ResourceType::Energy;
+    }
+
+    pub fn get_tasks(&self) -> Vec<Task> {
+        if let Ok(Some(tasks_string)) = self.creep.memory().string("tasks") {
+            if let Some(tasks) = codec::decode(&tasks_string) {
+                return tasks;
+            }
+            warn!("{}: discarding unreadable tasks", self.creep.name());
+        }
+        return vec![];
+    }
+
+    pub fn set_tasks(&self, tasks: &Vec<Task>) {
+        if tasks.is_empty() {
+            self.creep.memory().del("tasks");
+        } else {
+            self.creep.memory().set("tasks", codec::encode(tasks));
+        }
+    }
+
+    pub fn push_task(&self, task: Task) {
+        let mut tasks = self.get_tasks();
+        tasks.push(task);
+        self.set_tasks(&tasks);
     }
 
     fn get_new_mode(&self) -> Option<Mode> {
@@ -185,10 +296,56 @@ impl Creep {
         self.creep.store_free_capacity(None) == 0
     }
 
-    fn is_empty(&self) -> bool {
+    pub fn is_empty(&self) -> bool {
         self.creep.store_used_capacity(None) == 0
     }
 
+    pub fn name(&self) -> String {
+        self.creep.name()
+    }
+
+    pub fn id(&self) -> RawObjectId {
+        self.creep.untyped_id()
+    }
+
+    pub fn memory(&self) -> screeps::memory::MemoryReference {
+        self.creep.memory()
+    }
+
+    pub fn pos(&self) -> Position {
+        self.creep.pos()
+    }
+
+    pub fn body(&self) -> Vec<screeps::Part> {
+        self.creep.body().iter().map(|x| x.part).collect()
+    }
+
+    /// Body parts boosted with `compound`.
+    pub fn count_boosted_parts(&self, compound: ResourceType) -> u32 {
+        self.creep
+            .body()
+            .iter()
+            .filter(|x| x.boost == Some(compound))
+            .count() as u32
+    }
+
+    pub fn ticks_to_live(&self) -> u32 {
+        self.creep.ticks_to_live()
+    }
+
+    pub fn is_spawning(&self) -> bool {
+        self.creep.spawning()
+    }
+
+    /// The room the creep was spawned for, if known.
+    pub fn get_home(&self) -> Option<String> {
+        self.creep.memory().string("home").ok()?
+    }
+
+    pub fn room_name(&self) -> screeps::RoomName {
+        self.creep.pos().room_name()
+    }
+
     pub fn get_range_to<T>(&self, target: &T) -> u32
     where
         T: ?Sized + HasPosition,
@@ -197,15 +354,62 @@ impl Creep {
     }
 }
 
-pub fn game_loop() {
+fn get_role_from_name(name: &str) -> Result<Role> {
+    if !name.contains(":") {
+        return Err(Error::MalformedName(name.to_string()));
+    }
+    let name_prefix = name.split(":").next().unwrap_or_default();
+    return Role::from_str(name_prefix);
+}
+
+/// Gives a creep a new role, taking effect from the next tick. Its targets
+/// and tasks are dropped so the new role's allocator starts from scratch.
+pub fn set_role(creep: &screeps::Creep, role: &Role) {
+    let memory = creep.memory();
+    memory.set("role", role.to_string());
+    for key in &["mode", "input", "output", "tasks"] {
+        memory.del(key);
+    }
+    info!("{}: role set to {}", creep.name(), role.to_string());
+}
+
+/// Wraps every creep the bot can run. A creep that can't be interpreted, e.g.
+/// one spawned by hand, is reported once and then left alone.
+pub fn all() -> Vec<Creep> {
+    let mut result = vec![];
     for screeps_creep in screeps::game::creeps::values() {
-        let creep = Creep::new(screeps_creep);
+        match Creep::new(screeps_creep.clone()) {
+            Ok(creep) => {
+                if screeps_creep.memory().bool("quarantined") {
+                    info!("releasing {} from quarantine", screeps_creep.name());
+                    screeps_creep.memory().del("quarantined");
+                }
+                result.push(creep);
+            }
+            Err(error) => quarantine(&screeps_creep, &error),
+        }
+    }
+    return result;
+}
+
+fn quarantine(creep: &screeps::Creep, error: &Error) {
+    if creep.memory().bool("quarantined") {
+        return;
+    }
+    error!("quarantining creep {}: {}", creep.name(), error);
+    creep.memory().set("quarantined", true);
+}
+
+pub fn game_loop() {
+    for creep in all() {
         execute::execute(&creep);
     }
-    cleanup_memory().expect("expected Memory.creeps format to be a regular memory object");
+    if let Err(error) = cleanup_memory() {
+        error!("not cleaning game creep memory: {}", error);
+    }
 }
 
-fn cleanup_memory() -> Result<(), Box<dyn std::error::Error>> {
+fn cleanup_memory() -> Result<()> {
     let time = screeps::game::time();
     if time % 32 != 3 {
         return Ok(());
@@ -214,7 +418,10 @@ fn cleanup_memory() -> Result<(), Box<dyn std::error::Error>> {
     info!("running memory cleanup");
 
     let alive_creeps: HashSet<String> = screeps::game::creeps::keys().into_iter().collect();
-    let screeps_memory = match screeps::memory::root().dict("creeps")? {
+    let creeps_memory = screeps::memory::root()
+        .dict("creeps")
+        .map_err(|_| Error::UnexpectedMemoryType("creeps".to_string()))?;
+    let screeps_memory = match creeps_memory {
         Some(v) => v,
         None => {
             warn!("not cleaning game creep memory: no Memory.creeps dict");
@@ -0,0 +1,335 @@
+use crate::codec::{Decode, Decoder, Encode, Encoder};
+use log::*;
+use screeps::{
+    prelude::*, ConstructionSite, Position, RawObjectId, ResourceType, ReturnCode, Source,
+    Structure, StructureController, StructureLab, StructurePowerBank, StructureSpawn,
+};
+
+use super::Creep;
+
+/// A one-off job queued in creep memory, run ahead of the creep's mode.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Task {
+    MoveTo(Position),
+    Harvest(RawObjectId),
+    Withdraw(RawObjectId),
+    Transfer(RawObjectId),
+    Build(RawObjectId),
+    Upgrade(RawObjectId),
+    /// Walk to a spawn and be recycled for part of the body's energy.
+    Recycle(RawObjectId),
+    /// Walk to a spawn and be renewed until it stops adding ticks.
+    Renew(RawObjectId),
+    /// Walk towards a position until the creep enters its room.
+    MoveToRoom(Position),
+    /// Withdraw as much of one resource as the creep can carry.
+    WithdrawResource(RawObjectId, ResourceType),
+    /// Walk to a lab and be boosted by it.
+    Boost(RawObjectId),
+    /// Hit a power bank once.
+    Attack(RawObjectId),
+    /// Heal another creep once.
+    Heal(RawObjectId),
+}
+
+impl Task {
+    fn target_id(&self) -> Option<RawObjectId> {
+        return match self {
+            Task::MoveTo(_) | Task::MoveToRoom(_) => None,
+            Task::Harvest(id)
+            | Task::Withdraw(id)
+            | Task::Transfer(id)
+            | Task::Build(id)
+            | Task::Upgrade(id)
+            | Task::Recycle(id)
+            | Task::Renew(id)
+            | Task::WithdrawResource(id, _)
+            | Task::Boost(id)
+            | Task::Attack(id)
+            | Task::Heal(id) => Some(*id),
+        };
+    }
+
+    fn target_position(&self) -> Option<Position> {
+        if let Task::MoveTo(position) | Task::MoveToRoom(position) = self {
+            return Some(*position);
+        }
+        Some(screeps::game::get_object_erased(self.target_id()?)?.pos())
+    }
+}
+
+impl Encode for Task {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Task::MoveTo(position) => {
+                encoder.write_bits(0, 4);
+                position.encode(encoder);
+            }
+            Task::Harvest(id) => {
+                encoder.write_bits(1, 4);
+                id.encode(encoder);
+            }
+            Task::Withdraw(id) => {
+                encoder.write_bits(2, 4);
+                id.encode(encoder);
+            }
+            Task::Transfer(id) => {
+                encoder.write_bits(3, 4);
+                id.encode(encoder);
+            }
+            Task::Build(id) => {
+                encoder.write_bits(4, 4);
+                id.encode(encoder);
+            }
+            Task::Upgrade(id) => {
+                encoder.write_bits(5, 4);
+                id.encode(encoder);
+            }
+            Task::Recycle(id) => {
+                encoder.write_bits(6, 4);
+                id.encode(encoder);
+            }
+            Task::Renew(id) => {
+                encoder.write_bits(7, 4);
+                id.encode(encoder);
+            }
+            Task::MoveToRoom(position) => {
+                encoder.write_bits(8, 4);
+                position.encode(encoder);
+            }
+            Task::WithdrawResource(id, resource) => {
+                encoder.write_bits(9, 4);
+                id.encode(encoder);
+                resource.encode(encoder);
+            }
+            Task::Boost(id) => {
+                encoder.write_bits(10, 4);
+                id.encode(encoder);
+            }
+            Task::Attack(id) => {
+                encoder.write_bits(11, 4);
+                id.encode(encoder);
+            }
+            Task::Heal(id) => {
+                encoder.write_bits(12, 4);
+                id.encode(encoder);
+            }
+        }
+    }
+}
+
+impl Decode for Task {
+    fn decode(decoder: &mut Decoder) -> Option<Self> {
+        return match decoder.read_bits(4)? {
+            0 => Some(Task::MoveTo(Position::decode(decoder)?)),
+            1 => Some(Task::Harvest(RawObjectId::decode(decoder)?)),
+            2 => Some(Task::Withdraw(RawObjectId::decode(decoder)?)),
+            3 => Some(Task::Transfer(RawObjectId::decode(decoder)?)),
+            4 => Some(Task::Build(RawObjectId::decode(decoder)?)),
+            5 => Some(Task::Upgrade(RawObjectId::decode(decoder)?)),
+            6 => Some(Task::Recycle(RawObjectId::decode(decoder)?)),
+            7 => Some(Task::Renew(RawObjectId::decode(decoder)?)),
+            8 => Some(Task::MoveToRoom(Position::decode(decoder)?)),
+            9 => Some(Task::WithdrawResource(
+                RawObjectId::decode(decoder)?,
+                ResourceType::decode(decoder)?,
+            )),
+            10 => Some(Task::Boost(RawObjectId::decode(decoder)?)),
+            11 => Some(Task::Attack(RawObjectId::decode(decoder)?)),
+            12 => Some(Task::Heal(RawObjectId::decode(decoder)?)),
+            _ => None,
+        };
+    }
+}
+
+/// Runs the creep's first task. Returns false if the creep has no tasks.
+pub fn execute(creep: &Creep) -> bool {
+    let mut tasks = creep.get_tasks();
+    let task = match tasks.first() {
+        Some(task) => task.clone(),
+        None => return false,
+    };
+
+    let target_position = match task.target_position() {
+        Some(position) => position,
+        None => {
+            warn!(
+                "{}: dropping task without target {:?}",
+                creep.creep.name(),
+                task
+            );
+            tasks.remove(0);
+            creep.set_tasks(&tasks);
+            return true;
+        }
+    };
+
+    let return_code = execute_task(creep, &task);
+    match return_code {
+        ReturnCode::Ok | ReturnCode::Full | ReturnCode::NotEnough => {
+            if is_complete(creep, &task, return_code) {
+                tasks.remove(0);
+                creep.set_tasks(&tasks);
+            }
+        }
+        ReturnCode::NotInRange => {
+            creep.creep.move_to(&target_position);
+        }
+        _ => {
+            warn!(
+                "{}: dropping task {:?}: {:?}",
+                creep.creep.name(),
+                task,
+                return_code
+            );
+            tasks.remove(0);
+            creep.set_tasks(&tasks);
+        }
+    }
+    return true;
+}
+
+fn execute_task(creep: &Creep, task: &Task) -> ReturnCode {
+    return match task {
+        Task::MoveTo(position) => {
+            if creep.creep.pos() == *position {
+                ReturnCode::Ok
+            } else {
+                ReturnCode::NotInRange
+            }
+        }
+        Task::MoveToRoom(position) => {
+            if creep.room_name() == position.room_name() {
+                ReturnCode::Ok
+            } else {
+                ReturnCode::NotInRange
+            }
+        }
+        Task::Harvest(id) => match get_typed::<Source>(*id) {
+            Some(source) => creep.creep.harvest(&source),
+            None => ReturnCode::InvalidTarget,
+        },
+        Task::Withdraw(id) => match get_typed::<Structure>(*id) {
+            Some(structure) => match structure.as_withdrawable() {
+                Some(withdrawable) => creep.creep.withdraw_all(withdrawable, ResourceType::Energy),
+                None => ReturnCode::InvalidTarget,
+            },
+            None => ReturnCode::InvalidTarget,
+        },
+        Task::WithdrawResource(id, resource) => match get_typed::<Structure>(*id) {
+            Some(structure) => match structure.as_withdrawable() {
+                Some(withdrawable) => creep.creep.withdraw_all(withdrawable, *resource),
+                None => ReturnCode::InvalidTarget,
+            },
+            None => ReturnCode::InvalidTarget,
+        },
+        Task::Transfer(id) => match get_typed::<Structure>(*id) {
+            Some(structure) => match structure.as_transferable() {
+                Some(transferable) => {
+                    let resource = creep
+                        .creep
+                        .store_types()
+                        .first()
+                        .cloned()
+                        .unwrap_or(ResourceType::Energy);
+                    creep.creep.transfer_all(transferable, resource)
+                }
+                None => ReturnCode::InvalidTarget,
+            },
+            None => ReturnCode::InvalidTarget,
+        },
+        Task::Build(id) => match get_typed::<ConstructionSite>(*id) {
+            Some(site) => creep.creep.build(&site),
+            None => ReturnCode::InvalidTarget,
+        },
+        Task::Upgrade(id) => match get_typed::<StructureController>(*id) {
+            Some(controller) => creep.creep.upgrade_controller(&controller),
+            None => ReturnCode::InvalidTarget,
+        },
+        Task::Recycle(id) => match get_typed::<StructureSpawn>(*id) {
+            Some(spawn) => spawn.recycle_creep(&creep.creep),
+            None => ReturnCode::InvalidTarget,
+        },
+        Task::Renew(id) => match get_typed::<StructureSpawn>(*id) {
+            Some(spawn) => spawn.renew_creep(&creep.creep),
+            None => ReturnCode::InvalidTarget,
+        },
+        Task::Boost(id) => match get_typed::<StructureLab>(*id) {
+            Some(lab) => lab.boost_creep(&creep.creep, None),
+            None => ReturnCode::InvalidTarget,
+        },
+        Task::Attack(id) => match get_typed::<StructurePowerBank>(*id) {
+            Some(power_bank) => creep.creep.attack(&power_bank),
+            None => ReturnCode::InvalidTarget,
+        },
+        Task::Heal(id) => match get_typed::<screeps::Creep>(*id) {
+            Some(target) => creep.creep.heal(&target),
+            None => ReturnCode::InvalidTarget,
+        },
+    };
+}
+
+fn is_complete(creep: &Creep, task: &Task, return_code: ReturnCode) -> bool {
+    return match task {
+        Task::Harvest(_) => creep.is_full() || return_code == ReturnCode::Full,
+        Task::Build(_) | Task::Upgrade(_) => creep.is_empty(),
+        // Full once another renew would exceed the creep's lifetime.
+        Task::Renew(_) => return_code != ReturnCode::Ok,
+        _ => true,
+    };
+}
+
+fn get_typed<T>(id: RawObjectId) -> Option<T>
+where
+    T: screeps::SizedRoomObject + screeps::HasId,
+{
+    screeps::game::get_object_typed::<T>(id.into()).ok()?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec;
+    use screeps::RoomName;
+
+    #[test]
+    fn tasks_round_trip() {
+        let id: RawObjectId = "5bbcac4c9099fc012e6362f3".parse().unwrap();
+        let position = Position::new(12, 34, RoomName::new("W1N1").unwrap());
+        let tasks = vec![
+            Task::MoveTo(position),
+            Task::Harvest(id),
+            Task::Withdraw(id),
+            Task::Transfer(id),
+            Task::Build(id),
+            Task::Upgrade(id),
+            Task::Recycle(id),
+            Task::Renew(id),
+            Task::MoveToRoom(position),
+            Task::WithdrawResource(id, ResourceType::CatalyzedGhodiumAcid),
+            Task::Boost(id),
+            Task::Attack(id),
+            Task::Heal(id),
+        ];
+        for task in &tasks {
+            assert_eq!(codec::decode(&codec::encode(task)), Some(task.clone()));
+        }
+        assert_eq!(codec::decode(&codec::encode(&tasks)), Some(tasks));
+    }
+
+    #[test]
+    fn unknown_task_is_none() {
+        let mut encoder = Encoder::new();
+        encoder.write_bits(15, 4);
+        encoder.write_bits(0, 32);
+        assert_eq!(codec::decode::<Task>(&encoder.finish()), None);
+    }
+
+    #[test]
+    fn truncated_task_is_none() {
+        let position = Position::new(12, 34, RoomName::new("W1N1").unwrap());
+        let data = codec::encode(&Task::MoveTo(position));
+        let truncated: String = data.chars().take(1).collect();
+        assert_eq!(codec::decode::<Task>(&truncated), None);
+    }
+}
@@ -0,0 +1,79 @@
+//! Spending policy driven by how much energy a room has in storage.
+//!
+//! Rooms without storage spend as they go and are treated as `Normal`. So
+//! are rooms whose storage hasn't been filled past `LOW_STORAGE_ENERGY` yet,
+//! otherwise a newly built, empty storage would stop construction.
+
+use crate::rooms;
+use screeps::{prelude::*, ResourceType};
+
+#[derive(PartialEq, PartialOrd, Debug, Clone, Copy)]
+pub enum EnergyLevel {
+    /// Only keep the room alive: spawning and refilling.
+    Critical,
+    /// Finish what was started but don't start anything new.
+    Low,
+    Normal,
+    /// More than the room can use; spend it on upgrading and walls.
+    Surplus,
+}
+
+const CRITICAL_STORAGE_ENERGY: u32 = 10000;
+const LOW_STORAGE_ENERGY: u32 = 50000;
+const SURPLUS_STORAGE_ENERGY: u32 = 200000;
+
+pub fn get_energy_level(room: &screeps::Room) -> EnergyLevel {
+    let storage = match room.storage() {
+        Some(storage) => storage,
+        None => return EnergyLevel::Normal,
+    };
+    let energy = storage.store_of(ResourceType::Energy);
+    let room_name = room.name().to_string();
+    if !is_storage_filled(&room_name) {
+        if energy < LOW_STORAGE_ENERGY {
+            return EnergyLevel::Normal;
+        }
+        if let Some(memory) = rooms::memory(&room_name) {
+            memory.set("storage_filled", true);
+        }
+    }
+    if energy < CRITICAL_STORAGE_ENERGY {
+        return EnergyLevel::Critical;
+    }
+    if energy < LOW_STORAGE_ENERGY {
+        return EnergyLevel::Low;
+    }
+    if energy < SURPLUS_STORAGE_ENERGY {
+        return EnergyLevel::Normal;
+    }
+    return EnergyLevel::Surplus;
+}
+
+/// Whether the room's storage has held `LOW_STORAGE_ENERGY` before.
+fn is_storage_filled(room_name: &str) -> bool {
+    rooms::memory(room_name).map_or(false, |x| x.bool("storage_filled"))
+}
+
+/// New construction sites are only placed when there's energy to build them.
+pub fn can_start_construction(room: &screeps::Room) -> bool {
+    get_energy_level(room) >= EnergyLevel::Normal
+}
+
+/// Walls and ramparts are repaired up to this many hits.
+pub fn get_wall_target(room: &screeps::Room) -> u32 {
+    return match get_energy_level(room) {
+        EnergyLevel::Critical => 0,
+        EnergyLevel::Low => 10000,
+        EnergyLevel::Normal => 100000,
+        EnergyLevel::Surplus => 1000000,
+    };
+}
+
+/// Upgraders to run in a room that has storage.
+pub fn get_upgrader_count(room: &screeps::Room) -> u32 {
+    return match get_energy_level(room) {
+        EnergyLevel::Critical | EnergyLevel::Low => 1,
+        EnergyLevel::Normal => 2,
+        EnergyLevel::Surplus => 4,
+    };
+}
@@ -0,0 +1,30 @@
+use std::fmt;
+
+/// Errors raised while interpreting game objects and memory written by
+/// other code, older versions of the bot or by hand.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    UnknownRole(String),
+    MalformedName(String),
+    MalformedMemory { key: String, value: String },
+    UnexpectedMemoryType(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownRole(role) => write!(f, "unknown role '{}'", role),
+            Error::MalformedName(name) => write!(f, "malformed creep name '{}'", name),
+            Error::MalformedMemory { key, value } => {
+                write!(f, "malformed memory value '{}' for '{}'", value, key)
+            }
+            Error::UnexpectedMemoryType(path) => {
+                write!(f, "unexpected memory type at '{}'", path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
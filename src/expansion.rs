@@ -0,0 +1,355 @@
+//! Claiming new rooms once the GCL allows it.
+//!
+//! One expansion runs at a time and is tracked in `Memory.expansion`. The
+//! target is picked from room intel, a claimer takes its controller, and
+//! pioneers from the parent room build the first spawn. Expansions that take
+//! too long are recorded as failed and the room is skipped for a while.
+
+use crate::creeps::{self, Creep, Role, Task};
+use crate::intel::{self, RoomIntel};
+use crate::remote;
+use crate::spawn::{self, SpawnRequest};
+use log::*;
+use screeps::{find, prelude::*, Position, RoomName, StructureType, Terrain};
+
+const SELECT_INTERVAL: u32 = 500;
+/// Parents need to afford a claimer.
+const MINIMUM_PARENT_CAPACITY: u32 = 650;
+/// Candidates further than this many rooms from the parent are ignored.
+const MAX_DISTANCE: u32 = 5;
+const CLAIM_TIMEOUT: u32 = 5000;
+const BUILD_TIMEOUT: u32 = 20000;
+/// Failed rooms are skipped for this long.
+const FAILURE_COOLDOWN: u32 = 50000;
+const MAX_PIONEERS: usize = 3;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Status {
+    /// Waiting for a claimer to take the controller.
+    Claiming,
+    /// Controller is ours, pioneers are building the spawn.
+    Building,
+}
+
+const STATUS_STRINGS: [(Status, &'static str); 2] = [
+    (Status::Claiming, "claiming"),
+    (Status::Building, "building"),
+];
+
+#[derive(Debug, Clone)]
+pub struct Expansion {
+    pub target: String,
+    pub parent: String,
+    pub status: Status,
+    pub since: u32,
+}
+
+pub fn game_loop() {
+    let time = screeps::game::time();
+    match get_expansion() {
+        Some(expansion) => update(expansion, time),
+        None => {
+            if time % SELECT_INTERVAL == 0 {
+                start_expansion(time);
+            }
+        }
+    }
+}
+
+/// The expansion in progress, if any.
+pub fn get_expansion() -> Option<Expansion> {
+    let memory = screeps::memory::root().dict("expansion").ok()??;
+    let status_string = memory.string("status").ok()??;
+    let status = STATUS_STRINGS
+        .iter()
+        .find(|(_, x)| *x == status_string)
+        .map(|(status, _)| status.clone())?;
+    Some(Expansion {
+        target: memory.string("target").ok()??,
+        parent: memory.string("parent").ok()??,
+        status: status,
+        since: memory.i32("since").ok()?.unwrap_or(0) as u32,
+    })
+}
+
+/// Walks the creep into the room while it has no vision there. Returns true
+/// if the creep is travelling.
+pub fn travel_to_room(creep: &Creep, room_name: &str) -> bool {
+    let room_name = match RoomName::new(room_name) {
+        Ok(room_name) => room_name,
+        Err(_) => return false,
+    };
+    if creep.room_name() == room_name {
+        return false;
+    }
+    if creep.get_tasks().is_empty() {
+        creep.push_task(Task::MoveToRoom(Position::new(25, 25, room_name)));
+    }
+    return true;
+}
+
+fn update(mut expansion: Expansion, time: u32) {
+    let room = RoomName::new(&expansion.target)
+        .ok()
+        .and_then(|x| screeps::game::rooms::get(x));
+    let elapsed = time.saturating_sub(expansion.since);
+
+    match expansion.status {
+        Status::Claiming => {
+            let controller = room.as_ref().and_then(|x| x.controller());
+            if controller.as_ref().map_or(false, |x| x.my()) {
+                info!("claimed {}, building spawn", expansion.target);
+                expansion.status = Status::Building;
+                expansion.since = time;
+                save(Some(&expansion));
+                return;
+            }
+            request_creeps(&expansion, &Role::Claimer, 1);
+            let is_taken = controller.map_or(false, |x| {
+                x.owner_name().is_some()
+                    || x.reservation()
+                        .map_or(false, |x| Some(x.username) != remote::get_my_username())
+            });
+            if is_taken {
+                fail(&expansion, "taken by someone else");
+            } else if elapsed > CLAIM_TIMEOUT {
+                fail(&expansion, "claim timed out");
+            }
+        }
+        Status::Building => {
+            let room = match room {
+                Some(room) => room,
+                None => {
+                    if elapsed > BUILD_TIMEOUT {
+                        fail(&expansion, "spawn timed out");
+                    }
+                    return;
+                }
+            };
+            if !room.controller().map_or(false, |x| x.my()) {
+                fail(&expansion, "controller lost");
+                return;
+            }
+            if !room.find(find::MY_SPAWNS).is_empty() {
+                info!("expansion to {} complete", expansion.target);
+                save(None);
+                return;
+            }
+            if elapsed > BUILD_TIMEOUT {
+                fail(&expansion, "spawn timed out");
+                return;
+            }
+            place_spawn_site(&room);
+            request_creeps(&expansion, &Role::Pioneer, MAX_PIONEERS);
+        }
+    }
+}
+
+/// Queues creeps of a role in the parent room until `count` are alive or
+/// waiting to spawn.
+fn request_creeps(expansion: &Expansion, role: &Role, count: usize) {
+    let alive = creeps::all().iter().filter(|x| x.role == *role).count();
+    let queued = spawn::get_queue(&expansion.parent)
+        .iter()
+        .filter(|x| x.role == *role)
+        .count();
+    if alive + queued < count {
+        debug!("requesting {} for {}", role.to_string(), expansion.target);
+        spawn::queue_request(&expansion.parent, SpawnRequest::new(role.clone()));
+    }
+}
+
+fn start_expansion(time: u32) {
+    let owned_rooms: Vec<screeps::Room> = screeps::game::rooms::values()
+        .into_iter()
+        .filter(|x| x.controller().map_or(false, |x| x.my()))
+        .collect();
+    if owned_rooms.len() as u32 >= screeps::game::gcl::level() {
+        return;
+    }
+    let parents: Vec<&screeps::Room> = owned_rooms
+        .iter()
+        .filter(|x| x.energy_capacity_available() >= MINIMUM_PARENT_CAPACITY)
+        .filter(|x| !x.find(find::MY_SPAWNS).is_empty())
+        .collect();
+
+    let room_intel = intel::all();
+    let mut best: Option<(i32, String, String)> = None;
+    for candidate in &room_intel {
+        if !is_claimable(candidate) || is_recently_failed(&candidate.name, time) {
+            continue;
+        }
+        let candidate_name = match RoomName::new(&candidate.name) {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        let parent = parents
+            .iter()
+            .map(|x| {
+                let distance =
+                    screeps::game::map::get_room_linear_distance(x.name(), candidate_name, false);
+                (distance, x.name())
+            })
+            .min_by_key(|(distance, _)| *distance);
+        let (distance, parent_name) = match parent {
+            Some((distance, parent_name)) if distance <= MAX_DISTANCE => (distance, parent_name),
+            _ => continue,
+        };
+        let score = get_score(candidate, distance, &room_intel);
+        if best
+            .as_ref()
+            .map_or(true, |(best_score, _, _)| score > *best_score)
+        {
+            best = Some((score, candidate.name.clone(), parent_name.to_string()));
+        }
+    }
+
+    if let Some((score, target, parent)) = best {
+        info!("expanding to {} from {} (score {})", target, parent, score);
+        save(Some(&Expansion {
+            target: target,
+            parent: parent,
+            status: Status::Claiming,
+            since: time,
+        }));
+    }
+}
+
+fn is_claimable(room_intel: &RoomIntel) -> bool {
+    let controller = match &room_intel.controller {
+        Some(controller) => controller,
+        None => return false,
+    };
+    let is_reserved_by_others = controller
+        .reservation
+        .as_ref()
+        .map_or(false, |(username, _)| {
+            Some(username) != remote::get_my_username().as_ref()
+        });
+    return controller.owner.is_none()
+        && !is_reserved_by_others
+        && room_intel.hostile_structures == 0
+        && !room_intel.sources.is_empty();
+}
+
+/// Two sources matter most, then staying close to home and away from other
+/// players.
+fn get_score(candidate: &RoomIntel, distance: u32, room_intel: &[RoomIntel]) -> i32 {
+    let my_username = remote::get_my_username();
+    let hostile_neighbors = room_intel
+        .iter()
+        .filter(|x| {
+            x.controller
+                .as_ref()
+                .map_or(false, |x| x.owner.is_some() && x.owner != my_username)
+        })
+        .filter_map(|x| RoomName::new(&x.name).ok())
+        .filter(|x| {
+            RoomName::new(&candidate.name).map_or(false, |candidate_name| {
+                screeps::game::map::get_room_linear_distance(*x, candidate_name, false) <= 2
+            })
+        })
+        .count() as i32;
+    return candidate.sources.len() as i32 * 10 - distance as i32 * 2 - hostile_neighbors * 5;
+}
+
+/// Places the spawn on open ground near the middle of the sources and the
+/// controller.
+fn place_spawn_site(room: &screeps::Room) {
+    let has_site = room
+        .find(find::MY_CONSTRUCTION_SITES)
+        .iter()
+        .any(|x| x.structure_type() == StructureType::Spawn);
+    if has_site {
+        return;
+    }
+
+    let mut anchors: Vec<Position> = room.find(find::SOURCES).iter().map(|x| x.pos()).collect();
+    if let Some(controller) = room.controller() {
+        anchors.push(controller.pos());
+    }
+    if anchors.is_empty() {
+        return;
+    }
+    let center_x = anchors.iter().map(|x| x.x()).sum::<u32>() / anchors.len() as u32;
+    let center_y = anchors.iter().map(|x| x.y()).sum::<u32>() / anchors.len() as u32;
+
+    let terrain = room.get_terrain();
+    let is_open = |x: u32, y: u32| {
+        (x - 1..=x + 1).all(|x| (y - 1..=y + 1).all(|y| terrain.get(x, y) != Terrain::Wall))
+    };
+    let mut best: Option<(u32, u32, u32)> = None;
+    for x in 3..47 {
+        for y in 3..47 {
+            if !is_open(x, y) {
+                continue;
+            }
+            let range = std::cmp::max(
+                (x as i32 - center_x as i32).abs(),
+                (y as i32 - center_y as i32).abs(),
+            ) as u32;
+            if best.map_or(true, |(best_range, _, _)| range < best_range) {
+                best = Some((range, x, y));
+            }
+        }
+    }
+
+    if let Some((_, x, y)) = best {
+        let position = Position::new(x, y, room.name());
+        let return_code = room.create_construction_site(&position, StructureType::Spawn);
+        info!(
+            "placing spawn in {} at {:?}: {:?}",
+            room.name(),
+            position,
+            return_code
+        );
+    }
+}
+
+fn fail(expansion: &Expansion, reason: &str) {
+    warn!("expansion to {} failed: {}", expansion.target, reason);
+    if let Ok(failed) = screeps::memory::root()
+        .dict_or_create("expansion_failures")
+        .and_then(|x| x.dict_or_create(&expansion.target))
+    {
+        failed.set("time", screeps::game::time());
+        failed.set("reason", reason);
+    }
+    save(None);
+}
+
+fn is_recently_failed(room_name: &str, time: u32) -> bool {
+    let failure_time = screeps::memory::root()
+        .dict("expansion_failures")
+        .ok()
+        .flatten()
+        .and_then(|x| x.dict(room_name).ok()?)
+        .and_then(|x| x.i32("time").ok()?);
+    return match failure_time {
+        Some(failure_time) => time.saturating_sub(failure_time as u32) < FAILURE_COOLDOWN,
+        None => false,
+    };
+}
+
+fn save(expansion: Option<&Expansion>) {
+    let expansion = match expansion {
+        Some(expansion) => expansion,
+        None => {
+            screeps::memory::root().del("expansion");
+            return;
+        }
+    };
+    let memory = match screeps::memory::root().dict_or_create("expansion") {
+        Ok(memory) => memory,
+        Err(_) => return,
+    };
+    let status_string = STATUS_STRINGS
+        .iter()
+        .find(|(status, _)| *status == expansion.status)
+        .map(|(_, x)| *x)
+        .unwrap_or("claiming");
+    memory.set("target", expansion.target.as_str());
+    memory.set("parent", expansion.parent.as_str());
+    memory.set("status", status_string);
+    memory.set("since", expansion.since);
+}
@@ -0,0 +1,426 @@
+//! Factory commodity production.
+//!
+//! Rooms at RCL7 and up with a factory compress surplus minerals and energy
+//! into bars and batteries, and make higher commodities when the factory has
+//! the level for them. Every `PLAN_INTERVAL` ticks each factory picks the
+//! recipe it has spare inputs for that gains the most on the market; the
+//! recipe is kept in `Memory.rooms.<room>.factory`. Haulers bring the inputs
+//! and take the products to the terminal or storage.
+
+use crate::economy::{self, EnergyLevel};
+use crate::labs;
+use crate::market::{GameMarket, Market, OrderType};
+use crate::resources;
+use crate::rooms;
+use crate::terminal;
+use log::*;
+use screeps::{find, prelude::*, RawObjectId, ResourceType, ReturnCode, StructureFactory};
+use stdweb::{js, unstable::TryInto, Reference};
+
+const PLAN_INTERVAL: u32 = 100;
+const MIN_CONTROLLER_LEVEL: u32 = 7;
+/// Products are made until this much is in stock.
+const PRODUCT_TARGET: u32 = 3000;
+/// Products are taken out of the factory once it holds this much.
+const EMPTY_AMOUNT: u32 = 1000;
+/// The factory is kept stocked with inputs for this many runs.
+const INPUT_RUNS: u32 = 2;
+
+/// Minerals that are only compressed once the terminal has its target.
+const RAW_RESOURCES: [ResourceType; 8] = [
+    ResourceType::Hydrogen,
+    ResourceType::Oxygen,
+    ResourceType::Utrium,
+    ResourceType::Lemergium,
+    ResourceType::Keanium,
+    ResourceType::Zynthium,
+    ResourceType::Catalyst,
+    ResourceType::Ghodium,
+];
+
+/// One factory run: inputs turned into `amount` of `product`.
+#[derive(Debug)]
+pub struct Recipe {
+    pub product: ResourceType,
+    pub amount: u32,
+    /// The factory level the recipe needs, if any.
+    pub level: Option<u32>,
+    pub inputs: &'static [(ResourceType, u32)],
+}
+
+const RECIPES: [Recipe; 16] = [
+    Recipe {
+        product: ResourceType::UtriumBar,
+        amount: 100,
+        level: None,
+        inputs: &[(ResourceType::Utrium, 500), (ResourceType::Energy, 200)],
+    },
+    Recipe {
+        product: ResourceType::LemergiumBar,
+        amount: 100,
+        level: None,
+        inputs: &[(ResourceType::Lemergium, 500), (ResourceType::Energy, 200)],
+    },
+    Recipe {
+        product: ResourceType::ZynthiumBar,
+        amount: 100,
+        level: None,
+        inputs: &[(ResourceType::Zynthium, 500), (ResourceType::Energy, 200)],
+    },
+    Recipe {
+        product: ResourceType::KeaniumBar,
+        amount: 100,
+        level: None,
+        inputs: &[(ResourceType::Keanium, 500), (ResourceType::Energy, 200)],
+    },
+    Recipe {
+        product: ResourceType::GhodiumMelt,
+        amount: 100,
+        level: None,
+        inputs: &[(ResourceType::Ghodium, 500), (ResourceType::Energy, 200)],
+    },
+    Recipe {
+        product: ResourceType::Oxidant,
+        amount: 100,
+        level: None,
+        inputs: &[(ResourceType::Oxygen, 500), (ResourceType::Energy, 200)],
+    },
+    Recipe {
+        product: ResourceType::Reductant,
+        amount: 100,
+        level: None,
+        inputs: &[(ResourceType::Hydrogen, 500), (ResourceType::Energy, 200)],
+    },
+    Recipe {
+        product: ResourceType::Purifier,
+        amount: 100,
+        level: None,
+        inputs: &[(ResourceType::Catalyst, 500), (ResourceType::Energy, 200)],
+    },
+    Recipe {
+        product: ResourceType::Battery,
+        amount: 50,
+        level: None,
+        inputs: &[(ResourceType::Energy, 600)],
+    },
+    Recipe {
+        product: ResourceType::Wire,
+        amount: 20,
+        level: None,
+        inputs: &[
+            (ResourceType::UtriumBar, 20),
+            (ResourceType::Silicon, 100),
+            (ResourceType::Energy, 40),
+        ],
+    },
+    Recipe {
+        product: ResourceType::Cell,
+        amount: 20,
+        level: None,
+        inputs: &[
+            (ResourceType::LemergiumBar, 20),
+            (ResourceType::Biomass, 100),
+            (ResourceType::Energy, 40),
+        ],
+    },
+    Recipe {
+        product: ResourceType::Alloy,
+        amount: 20,
+        level: None,
+        inputs: &[
+            (ResourceType::ZynthiumBar, 20),
+            (ResourceType::Metal, 100),
+            (ResourceType::Energy, 40),
+        ],
+    },
+    Recipe {
+        product: ResourceType::Condensate,
+        amount: 20,
+        level: None,
+        inputs: &[
+            (ResourceType::KeaniumBar, 20),
+            (ResourceType::Mist, 100),
+            (ResourceType::Energy, 40),
+        ],
+    },
+    Recipe {
+        product: ResourceType::Composite,
+        amount: 20,
+        level: Some(1),
+        inputs: &[
+            (ResourceType::UtriumBar, 20),
+            (ResourceType::ZynthiumBar, 20),
+            (ResourceType::Energy, 20),
+        ],
+    },
+    Recipe {
+        product: ResourceType::Crystal,
+        amount: 6,
+        level: Some(2),
+        inputs: &[
+            (ResourceType::LemergiumBar, 6),
+            (ResourceType::KeaniumBar, 6),
+            (ResourceType::Purifier, 6),
+            (ResourceType::Energy, 45),
+        ],
+    },
+    Recipe {
+        product: ResourceType::Liquid,
+        amount: 12,
+        level: Some(3),
+        inputs: &[
+            (ResourceType::Oxidant, 12),
+            (ResourceType::Reductant, 12),
+            (ResourceType::GhodiumMelt, 12),
+            (ResourceType::Energy, 90),
+        ],
+    },
+];
+
+pub fn game_loop() {
+    let time = screeps::game::time();
+    let mut prices: Option<Vec<(ResourceType, f64)>> = None;
+    for room in screeps::game::rooms::values() {
+        let controller_level = match room.controller() {
+            Some(controller) if controller.my() => controller.level(),
+            _ => continue,
+        };
+        if controller_level < MIN_CONTROLLER_LEVEL {
+            continue;
+        }
+        let factory = match get_factory(&room) {
+            Some(factory) => factory,
+            None => continue,
+        };
+        let room_name = room.name().to_string();
+        if time % PLAN_INTERVAL == 0 {
+            let prices = prices.get_or_insert_with(|| get_prices(&GameMarket));
+            let recipe = choose_recipe(
+                get_factory_level(&factory),
+                &labs::get_stock(&room),
+                economy::get_energy_level(&room),
+                prices,
+            );
+            let product = recipe.map(|x| x.product);
+            if product != get_recipe(&room_name).map(|x| x.product) {
+                info!(
+                    "{}: factory switching to {:?}",
+                    room_name,
+                    product.map(resources::to_string)
+                );
+                set_recipe(&room_name, recipe);
+            }
+        }
+        if let Some(recipe) = get_recipe(&room_name) {
+            produce(&factory, recipe);
+            labs::request_hauler(&room_name);
+        }
+    }
+}
+
+pub fn get_recipe_for(product: ResourceType) -> Option<&'static Recipe> {
+    RECIPES.iter().find(|x| x.product == product)
+}
+
+/// The recipe a factory of `level` has spare inputs for that adds the most
+/// market value. Recipes worth less than their inputs are skipped; without
+/// any buy orders everything is worth nothing and the first recipe wins.
+pub fn choose_recipe(
+    level: u32,
+    stock: &[(ResourceType, u32)],
+    energy_level: EnergyLevel,
+    prices: &[(ResourceType, f64)],
+) -> Option<&'static Recipe> {
+    let mut best: Option<(f64, &'static Recipe)> = None;
+    for recipe in RECIPES.iter() {
+        if !can_make(recipe, level, stock, energy_level) {
+            continue;
+        }
+        let value = get_value(recipe, prices);
+        if value < 0.0 {
+            continue;
+        }
+        if best.map_or(true, |(best_value, _)| value > best_value) {
+            best = Some((value, recipe));
+        }
+    }
+    return best.map(|(_, recipe)| recipe);
+}
+
+fn can_make(
+    recipe: &Recipe,
+    level: u32,
+    stock: &[(ResourceType, u32)],
+    energy_level: EnergyLevel,
+) -> bool {
+    if recipe.level.map_or(false, |x| x != level) {
+        return false;
+    }
+    if get_amount(stock, recipe.product) >= PRODUCT_TARGET {
+        return false;
+    }
+    // Batteries store energy the room has no use for.
+    if recipe.product == ResourceType::Battery && energy_level < EnergyLevel::Surplus {
+        return false;
+    }
+    recipe
+        .inputs
+        .iter()
+        .all(|(resource, amount)| get_spare(*resource, stock, energy_level) >= *amount)
+}
+
+/// How much of a resource the factory may use up.
+fn get_spare(
+    resource: ResourceType,
+    stock: &[(ResourceType, u32)],
+    energy_level: EnergyLevel,
+) -> u32 {
+    let amount = get_amount(stock, resource);
+    if resource == ResourceType::Energy {
+        return if energy_level >= EnergyLevel::Normal {
+            amount
+        } else {
+            0
+        };
+    }
+    if RAW_RESOURCES.contains(&resource) {
+        return amount.saturating_sub(terminal::get_target(resource));
+    }
+    return amount;
+}
+
+/// Market value of one run's product less that of its inputs.
+fn get_value(recipe: &Recipe, prices: &[(ResourceType, f64)]) -> f64 {
+    let get_price = |resource: ResourceType| {
+        prices
+            .iter()
+            .find(|(x, _)| *x == resource)
+            .map_or(0.0, |(_, price)| *price)
+    };
+    let inputs: f64 = recipe
+        .inputs
+        .iter()
+        .map(|(resource, amount)| get_price(*resource) * *amount as f64)
+        .sum();
+    return get_price(recipe.product) * recipe.amount as f64 - inputs;
+}
+
+/// The best buy price of everything used or made by a recipe.
+fn get_prices(market: &dyn Market) -> Vec<(ResourceType, f64)> {
+    let mut resources: Vec<ResourceType> = vec![];
+    for recipe in RECIPES.iter() {
+        for resource in std::iter::once(recipe.product).chain(recipe.inputs.iter().map(|(x, _)| *x))
+        {
+            if !resources.contains(&resource) {
+                resources.push(resource);
+            }
+        }
+    }
+    resources
+        .into_iter()
+        .map(|resource| {
+            let price = market
+                .get_orders(resource, OrderType::Buy)
+                .iter()
+                .map(|x| x.price)
+                .fold(0.0, f64::max);
+            (resource, price)
+        })
+        .collect()
+}
+
+/// Hauler jobs for a room's factory as (from, resource, to): clearing out
+/// what the recipe doesn't use, then bringing its inputs.
+pub fn get_hauler_jobs(room: &screeps::Room) -> Vec<(RawObjectId, ResourceType, RawObjectId)> {
+    let mut result = vec![];
+    let factory = match get_factory(room) {
+        Some(factory) => factory,
+        None => return result,
+    };
+    let store_id = match labs::get_store_id(room) {
+        Some(store_id) => store_id,
+        None => return result,
+    };
+    let recipe = get_recipe(&room.name().to_string());
+
+    for resource in factory.store_types() {
+        let is_input = recipe.map_or(false, |x| x.inputs.iter().any(|(r, _)| *r == resource));
+        let is_product = recipe.map_or(false, |x| x.product == resource);
+        if !is_input && (!is_product || factory.store_of(resource) >= EMPTY_AMOUNT) {
+            result.push((factory.untyped_id(), resource, store_id));
+        }
+    }
+    if let Some(recipe) = recipe {
+        for (resource, amount) in recipe.inputs {
+            if factory.store_of(*resource) >= amount * INPUT_RUNS {
+                continue;
+            }
+            if let Some(source_id) = labs::get_source_of(room, *resource) {
+                result.push((source_id, *resource, factory.untyped_id()));
+            }
+        }
+    }
+    return result;
+}
+
+fn produce(factory: &StructureFactory, recipe: &Recipe) {
+    if factory.cooldown() > 0 {
+        return;
+    }
+    let has_inputs = recipe
+        .inputs
+        .iter()
+        .all(|(resource, amount)| factory.store_of(*resource) >= *amount);
+    if !has_inputs {
+        return;
+    }
+    let return_code = factory.produce(recipe.product);
+    if return_code != ReturnCode::Ok {
+        warn!(
+            "factory {:?} failed to make {}: {:?}",
+            factory.pos(),
+            resources::to_string(recipe.product),
+            return_code
+        );
+    }
+}
+
+fn get_factory(room: &screeps::Room) -> Option<StructureFactory> {
+    room.find(find::STRUCTURES)
+        .into_iter()
+        .find_map(|x| match x {
+            screeps::Structure::Factory(factory) if factory.my() => Some(factory),
+            _ => None,
+        })
+}
+
+/// The factory's level, 0 until a power creep sets it. The bindings don't
+/// have `level`, so it is read through JS.
+fn get_factory_level(factory: &StructureFactory) -> u32 {
+    let reference: &Reference = factory.as_ref();
+    let level = js! {
+        return @{reference}.level || 0;
+    };
+    return level.try_into().unwrap_or(0);
+}
+
+fn get_amount(stock: &[(ResourceType, u32)], resource: ResourceType) -> u32 {
+    stock
+        .iter()
+        .find(|(x, _)| *x == resource)
+        .map_or(0, |(_, amount)| *amount)
+}
+
+fn get_recipe(room_name: &str) -> Option<&'static Recipe> {
+    let product_string = rooms::memory(room_name)?.string("factory").ok()??;
+    get_recipe_for(resources::from_str(&product_string)?)
+}
+
+fn set_recipe(room_name: &str, recipe: Option<&Recipe>) {
+    if let Some(memory) = rooms::memory(room_name) {
+        match recipe {
+            Some(recipe) => memory.set("factory", resources::to_string(recipe.product)),
+            None => memory.del("factory"),
+        }
+    }
+}
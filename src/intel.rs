@@ -0,0 +1,277 @@
+//! What we know about rooms we can't currently see.
+//!
+//! Every visible room is recorded in a database kept in the `intel` memory
+//! segment. It is loaded once per VM and written back every few ticks when
+//! something changed.
+
+use crate::codec::{self, Decode, Decoder, Encode, Encoder};
+use crate::segments;
+use log::*;
+use screeps::{find, prelude::*, Attackable, Position, ResourceType, Structure, StructureType};
+use std::cell::RefCell;
+
+const SEGMENT_NAME: &str = "intel";
+const WRITE_INTERVAL: u32 = 20;
+/// Visible rooms are re-recorded at most this often.
+const UPDATE_INTERVAL: u32 = 10;
+/// Layout of the stored database. Version 0 rooms predate `power_bank`.
+const DATABASE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone)]
+pub struct ControllerIntel {
+    pub owner: Option<String>,
+    pub level: u32,
+    pub reservation: Option<(String, u32)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PowerBankIntel {
+    pub position: Position,
+    pub power: u32,
+    pub hits: u32,
+    /// The tick the bank disappears.
+    pub decays_at: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct RoomIntel {
+    pub name: String,
+    pub sources: Vec<Position>,
+    pub mineral: Option<ResourceType>,
+    pub controller: Option<ControllerIntel>,
+    pub hostile_structures: u32,
+    pub portals: Vec<Position>,
+    pub last_seen: u32,
+    pub power_bank: Option<PowerBankIntel>,
+}
+
+impl Encode for ControllerIntel {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.owner.encode(encoder);
+        self.level.encode(encoder);
+        self.reservation.encode(encoder);
+    }
+}
+
+impl Decode for ControllerIntel {
+    fn decode(decoder: &mut Decoder) -> Option<Self> {
+        Some(ControllerIntel {
+            owner: Decode::decode(decoder)?,
+            level: Decode::decode(decoder)?,
+            reservation: Decode::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for PowerBankIntel {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.position.encode(encoder);
+        self.power.encode(encoder);
+        self.hits.encode(encoder);
+        self.decays_at.encode(encoder);
+    }
+}
+
+impl Decode for PowerBankIntel {
+    fn decode(decoder: &mut Decoder) -> Option<Self> {
+        Some(PowerBankIntel {
+            position: Decode::decode(decoder)?,
+            power: Decode::decode(decoder)?,
+            hits: Decode::decode(decoder)?,
+            decays_at: Decode::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for RoomIntel {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.name.encode(encoder);
+        self.sources.encode(encoder);
+        self.mineral.encode(encoder);
+        self.controller.encode(encoder);
+        self.hostile_structures.encode(encoder);
+        self.portals.encode(encoder);
+        self.last_seen.encode(encoder);
+        self.power_bank.encode(encoder);
+    }
+}
+
+impl Decode for RoomIntel {
+    fn decode(decoder: &mut Decoder) -> Option<Self> {
+        Some(RoomIntel {
+            name: Decode::decode(decoder)?,
+            sources: Decode::decode(decoder)?,
+            mineral: Decode::decode(decoder)?,
+            controller: Decode::decode(decoder)?,
+            hostile_structures: Decode::decode(decoder)?,
+            portals: Decode::decode(decoder)?,
+            last_seen: Decode::decode(decoder)?,
+            power_bank: Decode::decode(decoder)?,
+        })
+    }
+}
+
+impl RoomIntel {
+    /// Reads a room recorded before power banks were tracked.
+    fn decode_version_0(decoder: &mut Decoder) -> Option<RoomIntel> {
+        Some(RoomIntel {
+            name: Decode::decode(decoder)?,
+            sources: Decode::decode(decoder)?,
+            mineral: Decode::decode(decoder)?,
+            controller: Decode::decode(decoder)?,
+            hostile_structures: Decode::decode(decoder)?,
+            portals: Decode::decode(decoder)?,
+            last_seen: Decode::decode(decoder)?,
+            power_bank: None,
+        })
+    }
+}
+
+struct Database {
+    rooms: Vec<RoomIntel>,
+    dirty: bool,
+}
+
+thread_local! {
+    static DATABASE: RefCell<Option<Database>> = RefCell::new(None);
+}
+
+pub fn game_loop() {
+    if !is_loaded() && !load() {
+        return;
+    }
+
+    let time = screeps::game::time();
+    for room in screeps::game::rooms::values() {
+        let room_name = room.name().to_string();
+        let is_recent = get(&room_name).map_or(false, |x| time - x.last_seen < UPDATE_INTERVAL);
+        if !is_recent {
+            record(observe(&room, time));
+        }
+    }
+
+    if time % WRITE_INTERVAL == 0 {
+        save();
+    }
+}
+
+pub fn get(room_name: &str) -> Option<RoomIntel> {
+    DATABASE.with(|x| {
+        x.borrow()
+            .as_ref()?
+            .rooms
+            .iter()
+            .find(|x| x.name == room_name)
+            .cloned()
+    })
+}
+
+pub fn all() -> Vec<RoomIntel> {
+    DATABASE.with(|x| match x.borrow().as_ref() {
+        Some(database) => database.rooms.clone(),
+        None => vec![],
+    })
+}
+
+/// The tick a room was last seen, or 0 if it never was.
+pub fn get_last_seen(room_name: &str) -> u32 {
+    get(room_name).map_or(0, |x| x.last_seen)
+}
+
+fn is_loaded() -> bool {
+    DATABASE.with(|x| x.borrow().is_some())
+}
+
+/// Loads the database once its segment is active. A missing segment starts
+/// an empty database; an unreadable one is discarded.
+fn load() -> bool {
+    let rooms = match segments::read(SEGMENT_NAME) {
+        Some(data) if data.is_empty() => vec![],
+        Some(data) => decode_rooms(&data).unwrap_or_else(|| {
+            warn!("discarding unreadable room intel");
+            vec![]
+        }),
+        None if !segments::exists(SEGMENT_NAME) => vec![],
+        None => return false,
+    };
+    info!("loaded intel for {} rooms", rooms.len());
+    DATABASE.with(|x| {
+        *x.borrow_mut() = Some(Database {
+            rooms: rooms,
+            dirty: false,
+        })
+    });
+    return true;
+}
+
+fn decode_rooms(data: &str) -> Option<Vec<RoomIntel>> {
+    let (version, mut decoder) = codec::decode_versioned(data)?;
+    match version {
+        0 => {
+            let length = decoder.read_varint()?;
+            let mut result = Vec::new();
+            for _ in 0..length {
+                result.push(RoomIntel::decode_version_0(&mut decoder)?);
+            }
+            return Some(result);
+        }
+        DATABASE_VERSION => return Decode::decode(&mut decoder),
+        _ => return None,
+    }
+}
+
+fn save() {
+    DATABASE.with(|x| {
+        if let Some(database) = x.borrow_mut().as_mut() {
+            if database.dirty {
+                let data = codec::encode_versioned(DATABASE_VERSION, &database.rooms);
+                segments::write(SEGMENT_NAME, &data);
+                database.dirty = false;
+            }
+        }
+    });
+}
+
+fn record(room_intel: RoomIntel) {
+    DATABASE.with(|x| {
+        if let Some(database) = x.borrow_mut().as_mut() {
+            database.rooms.retain(|x| x.name != room_intel.name);
+            database.rooms.push(room_intel);
+            database.dirty = true;
+        }
+    });
+}
+
+fn observe(room: &screeps::Room, time: u32) -> RoomIntel {
+    let controller = room.controller().map(|x| ControllerIntel {
+        owner: x.owner_name(),
+        level: x.level(),
+        reservation: x.reservation().map(|x| (x.username, x.ticks_to_end)),
+    });
+    let structures = room.find(find::STRUCTURES);
+    let portals = structures
+        .iter()
+        .filter(|x| x.structure_type() == StructureType::Portal)
+        .map(|x| x.pos())
+        .collect();
+    let power_bank = structures.iter().find_map(|x| match x {
+        Structure::PowerBank(power_bank) => Some(PowerBankIntel {
+            position: power_bank.pos(),
+            power: power_bank.power(),
+            hits: power_bank.hits(),
+            decays_at: time + power_bank.ticks_to_decay(),
+        }),
+        _ => None,
+    });
+
+    RoomIntel {
+        name: room.name().to_string(),
+        sources: room.find(find::SOURCES).iter().map(|x| x.pos()).collect(),
+        mineral: room.find(find::MINERALS).first().map(|x| x.mineral_type()),
+        controller: controller,
+        hostile_structures: room.find(find::HOSTILE_STRUCTURES).len() as u32,
+        portals: portals,
+        last_seen: time,
+        power_bank: power_bank,
+    }
+}
@@ -0,0 +1,670 @@
+//! Lab reactions.
+//!
+//! Labs are split into two input labs and the output labs in range of both.
+//! Each room works towards the compound in `TARGET_COMPOUNDS` it has least
+//! of, running whichever step of its reaction chain is possible with what is
+//! in storage, the terminal and the input labs. Haulers load the input labs
+//! and empty the output labs; the current reaction is kept in
+//! `Memory.rooms.<room>.reaction`. Output labs reserved for boosting are left
+//! out of reactions.
+
+use crate::boosts;
+use crate::creeps::{self, Role};
+use crate::resources;
+use crate::rooms;
+use crate::spawn::{self, SpawnRequest};
+use log::*;
+use screeps::{find, prelude::*, RawObjectId, ResourceType, ReturnCode, StructureLab};
+
+const PLAN_INTERVAL: u32 = 100;
+/// Reactions start once this much of both reagents is in stock, and
+/// intermediate compounds are produced up to this much.
+const BATCH_AMOUNT: u32 = 1000;
+/// Output labs are emptied once they hold this much.
+const EMPTY_AMOUNT: u32 = 1000;
+/// Labs react within this range of both input labs.
+const REACTION_RANGE: u32 = 2;
+
+/// Compounds to keep in stock, and how much of each.
+const TARGET_COMPOUNDS: [(ResourceType, u32); 6] = [
+    (ResourceType::CatalyzedGhodiumAlkalide, 3000),
+    (ResourceType::CatalyzedLemergiumAlkalide, 3000),
+    (ResourceType::CatalyzedZynthiumAlkalide, 3000),
+    (ResourceType::CatalyzedUtriumAcid, 3000),
+    (ResourceType::CatalyzedKeaniumAlkalide, 3000),
+    (ResourceType::CatalyzedGhodiumAcid, 3000),
+];
+
+/// Every reaction as (product, reagent, reagent).
+const REACTIONS: [(ResourceType, ResourceType, ResourceType); 34] = [
+    (
+        ResourceType::Hydroxide,
+        ResourceType::Hydrogen,
+        ResourceType::Oxygen,
+    ),
+    (
+        ResourceType::ZynthiumKeanite,
+        ResourceType::Zynthium,
+        ResourceType::Keanium,
+    ),
+    (
+        ResourceType::UtriumLemergite,
+        ResourceType::Utrium,
+        ResourceType::Lemergium,
+    ),
+    (
+        ResourceType::Ghodium,
+        ResourceType::ZynthiumKeanite,
+        ResourceType::UtriumLemergite,
+    ),
+    (
+        ResourceType::UtriumHydride,
+        ResourceType::Utrium,
+        ResourceType::Hydrogen,
+    ),
+    (
+        ResourceType::UtriumOxide,
+        ResourceType::Utrium,
+        ResourceType::Oxygen,
+    ),
+    (
+        ResourceType::KeaniumHydride,
+        ResourceType::Keanium,
+        ResourceType::Hydrogen,
+    ),
+    (
+        ResourceType::KeaniumOxide,
+        ResourceType::Keanium,
+        ResourceType::Oxygen,
+    ),
+    (
+        ResourceType::LemergiumHydride,
+        ResourceType::Lemergium,
+        ResourceType::Hydrogen,
+    ),
+    (
+        ResourceType::LemergiumOxide,
+        ResourceType::Lemergium,
+        ResourceType::Oxygen,
+    ),
+    (
+        ResourceType::ZynthiumHydride,
+        ResourceType::Zynthium,
+        ResourceType::Hydrogen,
+    ),
+    (
+        ResourceType::ZynthiumOxide,
+        ResourceType::Zynthium,
+        ResourceType::Oxygen,
+    ),
+    (
+        ResourceType::GhodiumHydride,
+        ResourceType::Ghodium,
+        ResourceType::Hydrogen,
+    ),
+    (
+        ResourceType::GhodiumOxide,
+        ResourceType::Ghodium,
+        ResourceType::Oxygen,
+    ),
+    (
+        ResourceType::UtriumAcid,
+        ResourceType::UtriumHydride,
+        ResourceType::Hydroxide,
+    ),
+    (
+        ResourceType::UtriumAlkalide,
+        ResourceType::UtriumOxide,
+        ResourceType::Hydroxide,
+    ),
+    (
+        ResourceType::KeaniumAcid,
+        ResourceType::KeaniumHydride,
+        ResourceType::Hydroxide,
+    ),
+    (
+        ResourceType::KeaniumAlkalide,
+        ResourceType::KeaniumOxide,
+        ResourceType::Hydroxide,
+    ),
+    (
+        ResourceType::LemergiumAcid,
+        ResourceType::LemergiumHydride,
+        ResourceType::Hydroxide,
+    ),
+    (
+        ResourceType::LemergiumAlkalide,
+        ResourceType::LemergiumOxide,
+        ResourceType::Hydroxide,
+    ),
+    (
+        ResourceType::ZynthiumAcid,
+        ResourceType::ZynthiumHydride,
+        ResourceType::Hydroxide,
+    ),
+    (
+        ResourceType::ZynthiumAlkalide,
+        ResourceType::ZynthiumOxide,
+        ResourceType::Hydroxide,
+    ),
+    (
+        ResourceType::GhodiumAcid,
+        ResourceType::GhodiumHydride,
+        ResourceType::Hydroxide,
+    ),
+    (
+        ResourceType::GhodiumAlkalide,
+        ResourceType::GhodiumOxide,
+        ResourceType::Hydroxide,
+    ),
+    (
+        ResourceType::CatalyzedUtriumAcid,
+        ResourceType::UtriumAcid,
+        ResourceType::Catalyst,
+    ),
+    (
+        ResourceType::CatalyzedUtriumAlkalide,
+        ResourceType::UtriumAlkalide,
+        ResourceType::Catalyst,
+    ),
+    (
+        ResourceType::CatalyzedKeaniumAcid,
+        ResourceType::KeaniumAcid,
+        ResourceType::Catalyst,
+    ),
+    (
+        ResourceType::CatalyzedKeaniumAlkalide,
+        ResourceType::KeaniumAlkalide,
+        ResourceType::Catalyst,
+    ),
+    (
+        ResourceType::CatalyzedLemergiumAcid,
+        ResourceType::LemergiumAcid,
+        ResourceType::Catalyst,
+    ),
+    (
+        ResourceType::CatalyzedLemergiumAlkalide,
+        ResourceType::LemergiumAlkalide,
+        ResourceType::Catalyst,
+    ),
+    (
+        ResourceType::CatalyzedZynthiumAcid,
+        ResourceType::ZynthiumAcid,
+        ResourceType::Catalyst,
+    ),
+    (
+        ResourceType::CatalyzedZynthiumAlkalide,
+        ResourceType::ZynthiumAlkalide,
+        ResourceType::Catalyst,
+    ),
+    (
+        ResourceType::CatalyzedGhodiumAcid,
+        ResourceType::GhodiumAcid,
+        ResourceType::Catalyst,
+    ),
+    (
+        ResourceType::CatalyzedGhodiumAlkalide,
+        ResourceType::GhodiumAlkalide,
+        ResourceType::Catalyst,
+    ),
+];
+
+/// One reaction: two reagents combined into a product.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Reaction {
+    pub product: ResourceType,
+    pub reagents: (ResourceType, ResourceType),
+}
+
+pub struct LabGroups {
+    pub inputs: (StructureLab, StructureLab),
+    pub outputs: Vec<StructureLab>,
+}
+
+pub fn game_loop() {
+    let time = screeps::game::time();
+    for room in screeps::game::rooms::values() {
+        if !room.controller().map_or(false, |x| x.my()) {
+            continue;
+        }
+        let lab_groups = match get_lab_groups(&room) {
+            Some(lab_groups) => lab_groups,
+            None => continue,
+        };
+        let room_name = room.name().to_string();
+        if time % PLAN_INTERVAL == 0 {
+            let reaction = choose_reaction(&get_reaction_stock(&room, &lab_groups));
+            if reaction != get_reaction(&room_name) {
+                info!("{}: switching reaction to {:?}", room_name, reaction);
+                set_reaction(&room_name, reaction);
+            }
+        }
+        let reaction = get_reaction(&room_name);
+        if let Some(reaction) = &reaction {
+            run_reactions(&room_name, &lab_groups, reaction);
+        }
+        if reaction.is_some() || !boosts::get_reservations(&room_name).is_empty() {
+            request_hauler(&room_name);
+        }
+    }
+}
+
+pub fn get_reagents(product: ResourceType) -> Option<(ResourceType, ResourceType)> {
+    REACTIONS
+        .iter()
+        .find(|(x, _, _)| *x == product)
+        .map(|(_, a, b)| (*a, *b))
+}
+
+/// The reactions leading to `product` from base minerals, each after the
+/// reactions producing its reagents.
+pub fn get_reaction_chain(product: ResourceType) -> Vec<Reaction> {
+    let mut result = vec![];
+    add_to_chain(product, &mut result);
+    return result;
+}
+
+fn add_to_chain(product: ResourceType, chain: &mut Vec<Reaction>) {
+    let reagents = match get_reagents(product) {
+        Some(reagents) => reagents,
+        None => return,
+    };
+    if chain.iter().any(|x| x.product == product) {
+        return;
+    }
+    add_to_chain(reagents.0, chain);
+    add_to_chain(reagents.1, chain);
+    chain.push(Reaction {
+        product: product,
+        reagents: reagents,
+    });
+}
+
+/// The next reaction towards `amount` of `product` given what is in stock.
+/// Steps closest to the product come first; intermediate compounds are only
+/// made up to a batch. `None` if there is enough already or no step has its
+/// reagents at hand.
+pub fn plan_reaction(
+    product: ResourceType,
+    amount: u32,
+    stock: &[(ResourceType, u32)],
+) -> Option<Reaction> {
+    let get_stock = |resource: ResourceType| {
+        stock
+            .iter()
+            .find(|(x, _)| *x == resource)
+            .map_or(0, |(_, amount)| *amount)
+    };
+    if get_stock(product) >= amount {
+        return None;
+    }
+    for reaction in get_reaction_chain(product).into_iter().rev() {
+        let goal = if reaction.product == product {
+            amount
+        } else {
+            BATCH_AMOUNT
+        };
+        if get_stock(reaction.product) >= goal {
+            continue;
+        }
+        let (first, second) = reaction.reagents;
+        if get_stock(first) >= BATCH_AMOUNT && get_stock(second) >= BATCH_AMOUNT {
+            return Some(reaction);
+        }
+    }
+    return None;
+}
+
+/// The next reaction for the target compound that is furthest below its
+/// stock level.
+pub fn choose_reaction(stock: &[(ResourceType, u32)]) -> Option<Reaction> {
+    let mut targets: Vec<(ResourceType, u32)> = TARGET_COMPOUNDS.to_vec();
+    targets.sort_by_key(|(product, amount)| {
+        let in_stock = stock
+            .iter()
+            .find(|(x, _)| x == product)
+            .map_or(0, |(_, x)| *x);
+        in_stock * 100 / amount
+    });
+    targets
+        .into_iter()
+        .find_map(|(product, amount)| plan_reaction(product, amount, stock))
+}
+
+/// Two input labs in range of as many other labs as possible, and those
+/// other labs as outputs.
+pub fn get_lab_groups(room: &screeps::Room) -> Option<LabGroups> {
+    let labs: Vec<StructureLab> = room
+        .find(find::STRUCTURES)
+        .into_iter()
+        .filter_map(|x| match x {
+            screeps::Structure::Lab(lab) if lab.my() => Some(lab),
+            _ => None,
+        })
+        .collect();
+    if labs.len() < 3 {
+        return None;
+    }
+
+    let mut best: Option<(usize, usize, usize)> = None;
+    for i in 0..labs.len() {
+        for j in i + 1..labs.len() {
+            let outputs = get_outputs(&labs, i, j).count();
+            if best.map_or(true, |(best_outputs, _, _)| outputs > best_outputs) {
+                best = Some((outputs, i, j));
+            }
+        }
+    }
+    let (_, i, j) = best?;
+    let outputs = get_outputs(&labs, i, j).cloned().collect();
+    Some(LabGroups {
+        inputs: (labs[i].clone(), labs[j].clone()),
+        outputs: outputs,
+    })
+}
+
+fn get_outputs<'a>(
+    labs: &'a [StructureLab],
+    i: usize,
+    j: usize,
+) -> impl Iterator<Item = &'a StructureLab> {
+    let first = labs[i].pos();
+    let second = labs[j].pos();
+    labs.iter().enumerate().filter_map(move |(k, lab)| {
+        let is_output = k != i
+            && k != j
+            && first.get_range_to(lab) <= REACTION_RANGE
+            && second.get_range_to(lab) <= REACTION_RANGE;
+        if is_output {
+            Some(lab)
+        } else {
+            None
+        }
+    })
+}
+
+/// Hauler jobs for a room's labs, most urgent first, as (from, resource, to).
+pub fn get_hauler_jobs(room: &screeps::Room) -> Vec<(RawObjectId, ResourceType, RawObjectId)> {
+    let mut result = vec![];
+    let lab_groups = match get_lab_groups(room) {
+        Some(lab_groups) => lab_groups,
+        None => return result,
+    };
+    let reaction = get_reaction(&room.name().to_string());
+    let store_id = match get_store_id(room) {
+        Some(store_id) => store_id,
+        None => return result,
+    };
+
+    let inputs = [
+        (&lab_groups.inputs.0, reaction.map(|x| x.reagents.0)),
+        (&lab_groups.inputs.1, reaction.map(|x| x.reagents.1)),
+    ];
+    for (lab, wanted) in inputs.iter() {
+        if let Some(mineral) = get_mineral(lab) {
+            if Some(mineral) != *wanted {
+                result.push((lab.untyped_id(), mineral, store_id));
+            }
+        }
+    }
+    let room_name = room.name().to_string();
+    // Boosts come first, a new creep is waiting on them.
+    for lab in &lab_groups.outputs {
+        let (compound, amount, energy) =
+            match boosts::get_lab_requirement(&room_name, lab.untyped_id()) {
+                Some(requirement) => requirement,
+                None => continue,
+            };
+        match get_mineral(lab) {
+            Some(mineral) if mineral != compound => {
+                result.insert(0, (lab.untyped_id(), mineral, store_id));
+                continue;
+            }
+            _ => {}
+        }
+        for (resource, needed) in [(compound, amount), (ResourceType::Energy, energy)].iter() {
+            if lab.store_of(*resource) >= *needed {
+                continue;
+            }
+            if let Some(source_id) = get_source_of(room, *resource) {
+                result.insert(0, (source_id, *resource, lab.untyped_id()));
+            }
+        }
+    }
+    for lab in &lab_groups.outputs {
+        if boosts::get_lab_requirement(&room_name, lab.untyped_id()).is_some() {
+            continue;
+        }
+        if let Some(mineral) = get_mineral(lab) {
+            let is_product = reaction.map_or(false, |x| x.product == mineral);
+            if !is_product || lab.store_of(mineral) >= EMPTY_AMOUNT {
+                result.push((lab.untyped_id(), mineral, store_id));
+            }
+        }
+    }
+    let stock = get_stock(room);
+    for (lab, wanted) in inputs.iter() {
+        let wanted = match wanted {
+            Some(wanted) => *wanted,
+            None => continue,
+        };
+        let has_room = lab.store_free_capacity(Some(wanted)) >= BATCH_AMOUNT;
+        let in_stock = stock.iter().any(|(x, amount)| *x == wanted && *amount > 0);
+        if has_room && in_stock {
+            if let Some(source_id) = get_source_of(room, wanted) {
+                result.push((source_id, wanted, lab.untyped_id()));
+            }
+        }
+    }
+    return result;
+}
+
+fn run_reactions(room_name: &str, lab_groups: &LabGroups, reaction: &Reaction) {
+    let (first, second) = &lab_groups.inputs;
+    if get_mineral(first) != Some(reaction.reagents.0)
+        || get_mineral(second) != Some(reaction.reagents.1)
+    {
+        return;
+    }
+    for lab in &lab_groups.outputs {
+        let is_reserved = boosts::get_lab_requirement(room_name, lab.untyped_id()).is_some();
+        if lab.cooldown() > 0 || is_reserved {
+            continue;
+        }
+        let return_code = lab.run_reaction(first, second);
+        if return_code != ReturnCode::Ok && return_code != ReturnCode::NotEnough {
+            debug!(
+                "lab {:?} failed to make {}: {:?}",
+                lab.pos(),
+                resources::to_string(reaction.product),
+                return_code
+            );
+        }
+    }
+}
+
+/// Queues a hauler for the room unless it has one.
+pub fn request_hauler(room_name: &str) {
+    let has_hauler = creeps::all()
+        .iter()
+        .any(|x| x.role == Role::Hauler && x.get_home().as_deref() == Some(room_name));
+    let is_queued = spawn::get_queue(room_name)
+        .iter()
+        .any(|x| x.role == Role::Hauler);
+    if !has_hauler && !is_queued {
+        spawn::queue_request(room_name, SpawnRequest::new(Role::Hauler));
+    }
+}
+
+fn get_mineral(lab: &StructureLab) -> Option<ResourceType> {
+    lab.store_types()
+        .into_iter()
+        .find(|x| *x != ResourceType::Energy)
+}
+
+/// Storage and terminal contents together.
+pub fn get_stock(room: &screeps::Room) -> Vec<(ResourceType, u32)> {
+    let mut result: Vec<(ResourceType, u32)> = vec![];
+    if let Some(storage) = room.storage() {
+        for resource in storage.store_types() {
+            add_to_stock(&mut result, resource, storage.store_of(resource));
+        }
+    }
+    if let Some(terminal) = room.terminal() {
+        for resource in terminal.store_types() {
+            add_to_stock(&mut result, resource, terminal.store_of(resource));
+        }
+    }
+    return result;
+}
+
+/// Stock for planning reactions. Reagents already loaded into the input
+/// labs count too, otherwise loading them can drop a reagent below a batch
+/// and switch the room to another reaction.
+fn get_reaction_stock(room: &screeps::Room, lab_groups: &LabGroups) -> Vec<(ResourceType, u32)> {
+    let mut result = get_stock(room);
+    for lab in [&lab_groups.inputs.0, &lab_groups.inputs.1].iter() {
+        if let Some(mineral) = get_mineral(lab) {
+            add_to_stock(&mut result, mineral, lab.store_of(mineral));
+        }
+    }
+    return result;
+}
+
+fn add_to_stock(stock: &mut Vec<(ResourceType, u32)>, resource: ResourceType, amount: u32) {
+    match stock.iter_mut().find(|(x, _)| *x == resource) {
+        Some((_, total)) => *total += amount,
+        None => stock.push((resource, amount)),
+    }
+}
+
+/// Where products and leftovers go: the terminal, or else storage.
+pub fn get_store_id(room: &screeps::Room) -> Option<RawObjectId> {
+    if let Some(terminal) = room.terminal() {
+        return Some(terminal.untyped_id());
+    }
+    return room.storage().map(|x| x.untyped_id());
+}
+
+/// The terminal or storage holding some of `resource`.
+pub fn get_source_of(room: &screeps::Room, resource: ResourceType) -> Option<RawObjectId> {
+    if let Some(terminal) = room.terminal() {
+        if terminal.store_of(resource) > 0 {
+            return Some(terminal.untyped_id());
+        }
+    }
+    if let Some(storage) = room.storage() {
+        if storage.store_of(resource) > 0 {
+            return Some(storage.untyped_id());
+        }
+    }
+    return None;
+}
+
+fn get_reaction(room_name: &str) -> Option<Reaction> {
+    let product_string = rooms::memory(room_name)?.string("reaction").ok()??;
+    let product = resources::from_str(&product_string)?;
+    Some(Reaction {
+        product: product,
+        reagents: get_reagents(product)?,
+    })
+}
+
+fn set_reaction(room_name: &str, reaction: Option<Reaction>) {
+    if let Some(memory) = rooms::memory(room_name) {
+        match reaction {
+            Some(reaction) => memory.set("reaction", resources::to_string(reaction.product)),
+            None => memory.del("reaction"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use screeps::ResourceType::*;
+
+    #[test]
+    fn chain_makes_reagents_before_products() {
+        let products: Vec<ResourceType> = get_reaction_chain(CatalyzedGhodiumAcid)
+            .iter()
+            .map(|x| x.product)
+            .collect();
+        assert_eq!(
+            products,
+            vec![
+                ZynthiumKeanite,
+                UtriumLemergite,
+                Ghodium,
+                GhodiumHydride,
+                Hydroxide,
+                GhodiumAcid,
+                CatalyzedGhodiumAcid,
+            ]
+        );
+    }
+
+    #[test]
+    fn chain_of_base_mineral_is_empty() {
+        assert!(get_reaction_chain(Hydrogen).is_empty());
+    }
+
+    #[test]
+    fn plan_prefers_step_closest_to_product() {
+        let stock = [
+            (GhodiumAcid, 1000),
+            (Catalyst, 1000),
+            (GhodiumHydride, 1000),
+            (Hydroxide, 1000),
+        ];
+        let reaction = plan_reaction(CatalyzedGhodiumAcid, 3000, &stock);
+        assert_eq!(reaction.map(|x| x.product), Some(CatalyzedGhodiumAcid));
+    }
+
+    #[test]
+    fn plan_makes_intermediates_up_to_a_batch() {
+        let stock = [
+            (GhodiumAcid, BATCH_AMOUNT - 1),
+            (GhodiumHydride, 1000),
+            (Hydroxide, 1000),
+        ];
+        let reaction = plan_reaction(CatalyzedGhodiumAcid, 3000, &stock);
+        assert_eq!(reaction.map(|x| x.product), Some(GhodiumAcid));
+
+        let stock = [
+            (GhodiumAcid, BATCH_AMOUNT),
+            (GhodiumHydride, 1000),
+            (Hydroxide, 1000),
+        ];
+        assert_eq!(plan_reaction(CatalyzedGhodiumAcid, 3000, &stock), None);
+    }
+
+    #[test]
+    fn plan_is_none_without_reagents() {
+        assert_eq!(plan_reaction(CatalyzedGhodiumAcid, 3000, &[]), None);
+        let stock = [(GhodiumAcid, 1000), (Catalyst, BATCH_AMOUNT - 1)];
+        assert_eq!(plan_reaction(CatalyzedGhodiumAcid, 3000, &stock), None);
+    }
+
+    #[test]
+    fn plan_is_none_with_enough_product() {
+        let stock = [
+            (CatalyzedGhodiumAcid, 3000),
+            (GhodiumAcid, 1000),
+            (Catalyst, 1000),
+        ];
+        assert_eq!(plan_reaction(CatalyzedGhodiumAcid, 3000, &stock), None);
+    }
+
+    #[test]
+    fn choose_works_on_target_furthest_below_stock_level() {
+        let stock = [
+            (CatalyzedGhodiumAlkalide, 2000),
+            (GhodiumAlkalide, 1000),
+            (UtriumAcid, 1000),
+            (Catalyst, 2000),
+        ];
+        let reaction = choose_reaction(&stock);
+        assert_eq!(reaction.map(|x| x.product), Some(CatalyzedUtriumAcid));
+    }
+}
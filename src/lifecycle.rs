@@ -0,0 +1,210 @@
+//! Creep replacement, renewal and recycling.
+//!
+//! A replacement is queued early enough that it is spawned and has walked to
+//! the old creep's workplace just as the old creep expires, so sources are
+//! never left uncovered. Creeps much smaller than the room can now build are
+//! recycled, and large creeps near death are renewed while the spawn is idle.
+
+use crate::allocator;
+use crate::creeps::{Creep, Role, Task};
+use crate::spawn::{self, SpawnRequest};
+use log::*;
+use screeps::{find, prelude::*, Position, RoomName, StructureSpawn};
+use std::collections::HashMap;
+
+/// CREEP_SPAWN_TIME
+const SPAWN_TIME_PER_PART: u32 = 3;
+/// Renew creeps whose body cost at least this much when they get this old.
+const RENEW_MINIMUM_BODY_COST: u32 = 1000;
+const RENEW_TICKS_TO_LIVE: u32 = 300;
+
+/// What retirement decisions need from a room with spawns, read once a tick.
+struct SpawnRoom {
+    room: screeps::Room,
+    spawns: Vec<StructureSpawn>,
+    capacity: u32,
+    is_queue_empty: bool,
+}
+
+pub fn game_loop(creeps: &[Creep]) {
+    // Recycle one creep per room at a time so the room isn't left empty.
+    let mut recycling_rooms: Vec<screeps::RoomName> = creeps
+        .iter()
+        .filter(|x| x.get_tasks().iter().any(|x| matches!(x, Task::Recycle(_))))
+        .map(|x| x.room_name())
+        .collect();
+    let mut spawn_rooms: HashMap<RoomName, Option<SpawnRoom>> = HashMap::new();
+    for creep in creeps {
+        if creep.is_spawning() || !creep.get_tasks().is_empty() {
+            continue;
+        }
+        let spawn_room = spawn_rooms
+            .entry(creep.room_name())
+            .or_insert_with(|| get_spawn_room(creep.room_name()));
+        let spawn_room = match spawn_room {
+            Some(spawn_room) => spawn_room,
+            None => continue,
+        };
+        let can_recycle = !recycling_rooms.contains(&creep.room_name());
+        if plan_retirement(creep, spawn_room, can_recycle) {
+            recycling_rooms.push(creep.room_name());
+        }
+    }
+
+    for creep in creeps {
+        if !is_replaced_before_death(&creep.role) || creep.is_spawning() {
+            continue;
+        }
+        if creep.memory().bool("replacement_queued") {
+            continue;
+        }
+        let home = match creep.get_home() {
+            Some(home) => home,
+            None => creep.room_name().to_string(),
+        };
+        let travel_time = match get_travel_time(creep, &home) {
+            Some(travel_time) => travel_time,
+            None => continue,
+        };
+
+        let lead_time = get_spawn_time(creep) + travel_time;
+        if creep.ticks_to_live() <= lead_time {
+            info!(
+                "{}: queueing replacement, {} ticks to live",
+                creep.name(),
+                creep.ticks_to_live()
+            );
+            let mut request = SpawnRequest::new(creep.role.clone());
+            request.replaces = Some(creep.name());
+            spawn::queue_request(&home, request);
+            creep.memory().set("replacement_queued", true);
+        }
+    }
+}
+
+/// Sends an outdated creep to be recycled, or a valuable old one to be
+/// renewed. Returns true if the creep is sent to be recycled.
+fn plan_retirement(creep: &Creep, spawn_room: &SpawnRoom, can_recycle: bool) -> bool {
+    let room = &spawn_room.room;
+    let spawn = match spawn_room
+        .spawns
+        .iter()
+        .min_by_key(|x| creep.get_range_to(*x))
+    {
+        Some(spawn) => spawn,
+        None => return false,
+    };
+
+    let body_cost = get_body_cost(&creep.body());
+    let current_body_cost = get_body_cost(&allocator::get_body(&creep.role, spawn_room.capacity));
+    let is_outdated = body_cost * 2 <= current_body_cost;
+
+    // Only recycle when a replacement can be afforded straight away.
+    if is_outdated && can_recycle && room.energy_available() >= room.energy_capacity_available() {
+        info!(
+            "{}: recycling, body cost {} of {}",
+            creep.name(),
+            body_cost,
+            current_body_cost
+        );
+        creep.push_task(Task::Recycle(spawn.untyped_id()));
+        return true;
+    }
+
+    let is_spawn_idle = !spawn.is_spawning() && spawn_room.is_queue_empty;
+    if !is_outdated
+        && is_spawn_idle
+        && body_cost >= RENEW_MINIMUM_BODY_COST
+        && creep.ticks_to_live() < RENEW_TICKS_TO_LIVE
+        && !creep.memory().bool("replacement_queued")
+    {
+        info!("{}: renewing", creep.name());
+        creep.push_task(Task::Renew(spawn.untyped_id()));
+    }
+    return false;
+}
+
+fn get_spawn_room(room_name: RoomName) -> Option<SpawnRoom> {
+    let room = screeps::game::rooms::get(room_name)?;
+    let spawns = room.find(find::MY_SPAWNS);
+    if spawns.is_empty() {
+        return None;
+    }
+    Some(SpawnRoom {
+        capacity: spawn::get_spawn_capacity(&room),
+        is_queue_empty: spawn::get_queue(&room_name.to_string()).is_empty(),
+        spawns: spawns,
+        room: room,
+    })
+}
+
+fn get_body_cost(body: &[screeps::Part]) -> u32 {
+    body.iter().map(|x| x.cost()).sum()
+}
+
+/// True if the creep's successor is alive and about to take its place, so
+/// the pair only counts once.
+pub fn has_live_successor(creep: &Creep) -> bool {
+    match creep.memory().string("successor") {
+        Ok(Some(successor)) => screeps::game::creeps::get(&successor).is_some(),
+        _ => false,
+    }
+}
+
+/// The workplace of the creep being replaced, for its successor to take over.
+pub fn get_predecessor_input(creep: &Creep) -> Option<screeps::RawObjectId> {
+    let predecessor_name = creep.memory().string("replaces").ok()??;
+    let predecessor = Creep::new(screeps::game::creeps::get(&predecessor_name)?).ok()?;
+    return predecessor.get_stored_id("input");
+}
+
+fn is_replaced_before_death(role: &Role) -> bool {
+    return match role {
+        Role::Harvester => true,
+        Role::Worker
+        | Role::MineralMiner
+        | Role::Reserver
+        | Role::RemoteMiner
+        | Role::RemoteHauler
+        | Role::Scout
+        | Role::Claimer
+        | Role::Pioneer
+        | Role::Upgrader
+        | Role::Hauler
+        | Role::PowerAttacker
+        | Role::PowerHealer
+        | Role::PowerCarrier => false,
+    };
+}
+
+fn get_spawn_time(creep: &Creep) -> u32 {
+    creep.body().len() as u32 * SPAWN_TIME_PER_PART
+}
+
+/// Path length from the home spawn to the creep's workplace, measured once
+/// and kept in creep memory.
+fn get_travel_time(creep: &Creep, home: &str) -> Option<u32> {
+    if let Ok(Some(travel_time)) = creep.memory().i32("travel_time") {
+        return Some(travel_time as u32);
+    }
+
+    let input_id = creep.get_stored_id("input")?;
+    let workplace = screeps::game::get_object_erased(input_id)?.pos();
+    let spawn_position = find_home_spawn_position(home)?;
+    let travel_time = measure_path_length(spawn_position, workplace);
+    debug!("{}: travel time {}", creep.name(), travel_time);
+    creep.memory().set("travel_time", travel_time);
+    return Some(travel_time);
+}
+
+fn find_home_spawn_position(home: &str) -> Option<Position> {
+    let room_name = screeps::RoomName::new(home).ok()?;
+    let room = screeps::game::rooms::get(room_name)?;
+    return Some(room.find(find::MY_SPAWNS).first()?.pos());
+}
+
+fn measure_path_length(from: Position, to: Position) -> u32 {
+    let search_results =
+        screeps::pathfinder::search(&from, &to, 1, screeps::pathfinder::SearchOptions::default());
+    return search_results.load_local_path().len() as u32;
+}
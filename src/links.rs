@@ -0,0 +1,258 @@
+//! Links at sources, the controller and storage.
+//!
+//! Source links collect energy from harvesters and send it on to the
+//! controller link while upgraders need it, otherwise to the storage link,
+//! which haulers empty into storage. New link sites are planned every so
+//! often as the RCL allows more links.
+
+use crate::labs;
+use log::*;
+use screeps::{
+    find, look, prelude::*, Position, RawObjectId, ResourceType, ReturnCode, StructureLink,
+    StructureType, Terrain,
+};
+
+const PLAN_INTERVAL: u32 = 100;
+/// Source links wait until they have this much to keep transfers efficient.
+const MINIMUM_SEND_AMOUNT: u32 = 400;
+/// Links within this range of a source, the controller or storage belong to it.
+const SOURCE_RANGE: u32 = 2;
+const CONTROLLER_RANGE: u32 = 3;
+const STORAGE_RANGE: u32 = 2;
+
+/// The links of one room, by purpose.
+pub struct Links {
+    pub sources: Vec<StructureLink>,
+    pub controller: Option<StructureLink>,
+    pub storage: Option<StructureLink>,
+}
+
+pub fn game_loop() {
+    let time = screeps::game::time();
+    for room in screeps::game::rooms::values() {
+        if !room.controller().map_or(false, |x| x.my()) {
+            continue;
+        }
+        transfer(&get_links(&room));
+        if !get_hauler_jobs(&room).is_empty() {
+            labs::request_hauler(&room.name().to_string());
+        }
+        if time % PLAN_INTERVAL == 0 {
+            plan(&room);
+        }
+    }
+}
+
+pub fn get_links(room: &screeps::Room) -> Links {
+    let links: Vec<StructureLink> = room
+        .find(find::STRUCTURES)
+        .into_iter()
+        .filter_map(|x| match x {
+            screeps::Structure::Link(link) if link.my() => Some(link),
+            _ => None,
+        })
+        .collect();
+    let sources = room.find(find::SOURCES);
+    let controller = room.controller();
+    let storage = room.storage();
+
+    let mut result = Links {
+        sources: vec![],
+        controller: None,
+        storage: None,
+    };
+    for link in links {
+        let is_controller_link = controller
+            .as_ref()
+            .map_or(false, |x| x.pos().get_range_to(&link) <= CONTROLLER_RANGE);
+        let is_storage_link = storage
+            .as_ref()
+            .map_or(false, |x| x.pos().get_range_to(&link) <= STORAGE_RANGE);
+        if is_controller_link && result.controller.is_none() {
+            result.controller = Some(link);
+        } else if is_storage_link && result.storage.is_none() {
+            result.storage = Some(link);
+        } else if sources
+            .iter()
+            .any(|x| x.pos().get_range_to(&link) <= SOURCE_RANGE)
+        {
+            result.sources.push(link);
+        }
+    }
+    return result;
+}
+
+/// The link next to a source, for its harvesters to fill.
+pub fn get_source_link(source: &screeps::Source) -> Option<StructureLink> {
+    get_links(&source.room())
+        .sources
+        .into_iter()
+        .find(|x| source.pos().get_range_to(x) <= SOURCE_RANGE)
+}
+
+/// Hauler jobs to empty the storage link into storage, as (from, resource,
+/// to).
+pub fn get_hauler_jobs(room: &screeps::Room) -> Vec<(RawObjectId, ResourceType, RawObjectId)> {
+    let mut result = vec![];
+    let storage = match room.storage() {
+        Some(storage) => storage,
+        None => return result,
+    };
+    if let Some(link) = get_links(room).storage {
+        if link.store_of(ResourceType::Energy) >= MINIMUM_SEND_AMOUNT {
+            result.push((
+                link.untyped_id(),
+                ResourceType::Energy,
+                storage.untyped_id(),
+            ));
+        }
+    }
+    return result;
+}
+
+fn transfer(links: &Links) {
+    for source_link in &links.sources {
+        let energy = source_link.store_of(ResourceType::Energy);
+        if source_link.cooldown() > 0 || energy < MINIMUM_SEND_AMOUNT {
+            continue;
+        }
+
+        // Upgraders come first; storage takes whatever they can't use.
+        let target = [&links.controller, &links.storage]
+            .iter()
+            .filter_map(|x| x.as_ref())
+            .find(|x| x.store_free_capacity(Some(ResourceType::Energy)) >= MINIMUM_SEND_AMOUNT);
+        let target = match target {
+            Some(target) => target,
+            None => continue,
+        };
+        let amount = std::cmp::min(
+            energy,
+            target.store_free_capacity(Some(ResourceType::Energy)),
+        );
+        let return_code = source_link.transfer_energy(target, Some(amount));
+        if return_code != ReturnCode::Ok {
+            warn!(
+                "link {:?} failed to send {} energy: {:?}",
+                source_link.pos(),
+                amount,
+                return_code
+            );
+        }
+    }
+}
+
+/// Places the next link site: the controller first, then sources, then
+/// storage.
+fn plan(room: &screeps::Room) {
+    let controller = match room.controller() {
+        Some(controller) => controller,
+        None => return,
+    };
+    let existing = room
+        .find(find::MY_STRUCTURES)
+        .iter()
+        .filter(|x| x.structure_type() == StructureType::Link)
+        .count()
+        + room
+            .find(find::MY_CONSTRUCTION_SITES)
+            .iter()
+            .filter(|x| x.structure_type() == StructureType::Link)
+            .count();
+    if existing >= get_max_links(controller.level()) {
+        return;
+    }
+    let spawn_position = match room.find(find::MY_SPAWNS).first() {
+        Some(spawn) => spawn.pos(),
+        None => return,
+    };
+
+    let mut anchors: Vec<(Position, u32)> = vec![(controller.pos(), CONTROLLER_RANGE)];
+    for source in room.find(find::SOURCES) {
+        anchors.push((source.pos(), SOURCE_RANGE));
+    }
+    if let Some(storage) = room.storage() {
+        anchors.push((storage.pos(), STORAGE_RANGE));
+    }
+
+    for (anchor, range) in anchors {
+        if has_link_near(room, anchor, range) {
+            continue;
+        }
+        // Next to where creeps working the anchor stand, off the path there.
+        let path = screeps::pathfinder::search(
+            &spawn_position,
+            &anchor,
+            range - 1,
+            screeps::pathfinder::SearchOptions::default(),
+        )
+        .load_local_path();
+        let work_position = match path.last() {
+            Some(position) => *position,
+            None => continue,
+        };
+        let position = match find_free_adjacent(room, work_position, &path) {
+            Some(position) => position,
+            None => continue,
+        };
+        let return_code = room.create_construction_site(&position, StructureType::Link);
+        info!(
+            "placing link in {} at {:?}: {:?}",
+            room.name(),
+            position,
+            return_code
+        );
+        return;
+    }
+}
+
+fn get_max_links(level: u32) -> usize {
+    return match level {
+        5 => 2,
+        6 => 3,
+        7 => 4,
+        8 => 6,
+        _ => 0,
+    };
+}
+
+fn has_link_near(room: &screeps::Room, position: Position, range: u32) -> bool {
+    let has_link = room
+        .find(find::MY_STRUCTURES)
+        .iter()
+        .any(|x| x.structure_type() == StructureType::Link && position.get_range_to(x) <= range);
+    let has_site = room
+        .find(find::MY_CONSTRUCTION_SITES)
+        .iter()
+        .any(|x| x.structure_type() == StructureType::Link && position.get_range_to(x) <= range);
+    return has_link || has_site;
+}
+
+fn find_free_adjacent(
+    room: &screeps::Room,
+    center: Position,
+    excluded: &[Position],
+) -> Option<Position> {
+    let terrain = room.get_terrain();
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let x = center.x() as i32 + dx;
+            let y = center.y() as i32 + dy;
+            if (dx == 0 && dy == 0) || x < 1 || y < 1 || x > 48 || y > 48 {
+                continue;
+            }
+            let position = Position::new(x as u32, y as u32, room.name());
+            if terrain.get(x as u32, y as u32) == Terrain::Wall || excluded.contains(&position) {
+                continue;
+            }
+            let is_occupied = !room.look_for_at(look::STRUCTURES, &position).is_empty()
+                || !room
+                    .look_for_at(look::CONSTRUCTION_SITES, &position)
+                    .is_empty();
+            if !is_occupied {
+                return Some(position);
+            }
+        }
+    }
+    return None;
+}
@@ -1,3 +1,6 @@
+use screeps::memory::MemoryReference;
+use std::cell::RefCell;
+use std::str::FromStr;
 use stdweb::js;
 
 pub use log::LevelFilter::*;
@@ -5,6 +8,46 @@ pub use log::LevelFilter::*;
 struct JsLog;
 struct JsNotify;
 
+/// Log levels read from `Memory.log`, for example:
+/// `{ level: "info", targets: { "crate::creeps::execute": "warn" } }`.
+struct LogConfig {
+    level: log::LevelFilter,
+    targets: Vec<(String, log::LevelFilter)>,
+}
+
+thread_local! {
+    static DEFAULT_LEVEL: RefCell<log::LevelFilter> = RefCell::new(log::LevelFilter::Info);
+    static CONFIG: RefCell<LogConfig> = RefCell::new(LogConfig {
+        level: log::LevelFilter::Info,
+        targets: vec![],
+    });
+}
+
+impl LogConfig {
+    fn level_for(&self, target: &str) -> log::LevelFilter {
+        // The longest matching module prefix wins.
+        let mut result = self.level;
+        let mut matched_length = 0;
+        for (prefix, level) in &self.targets {
+            let is_match = target == prefix
+                || (target.starts_with(prefix.as_str())
+                    && target[prefix.len()..].starts_with("::"));
+            if is_match && prefix.len() >= matched_length {
+                result = *level;
+                matched_length = prefix.len();
+            }
+        }
+        return result;
+    }
+
+    fn max_level(&self) -> log::LevelFilter {
+        self.targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, std::cmp::max)
+    }
+}
+
 impl log::Log for JsLog {
     fn enabled(&self, _: &log::Metadata<'_>) -> bool {
         true
@@ -17,22 +60,39 @@ impl log::Log for JsLog {
     }
     fn flush(&self) {}
 }
+/// Queues records for the next notification digest instead of sending them
+/// straight away. Identical messages are merged and counted.
 impl log::Log for JsNotify {
     fn enabled(&self, _: &log::Metadata<'_>) -> bool {
         true
     }
     fn log(&self, record: &log::Record<'_>) {
-        let message = format!("{}", record.args());
-        js! {
-            Game.notify(@{message});
+        // Already formatted with level and target by the outer dispatch.
+        let key = format!("{}", record.args());
+        let notify_memory = match get_notify_memory() {
+            Some(notify_memory) => notify_memory,
+            None => return,
+        };
+        if let Ok(pending) = notify_memory.dict_or_create("pending") {
+            let count = pending.i32(&key).ok().flatten().unwrap_or(0);
+            if count == 0 {
+                if let Ok(first_seen) = notify_memory.dict_or_create("first_seen") {
+                    first_seen.set(&key, screeps::game::time());
+                }
+            }
+            pending.set(&key, count + 1);
         }
     }
     fn flush(&self) {}
 }
 
 pub fn setup_logging(verbosity: log::LevelFilter) {
+    DEFAULT_LEVEL.with(|x| *x.borrow_mut() = verbosity);
+    CONFIG.with(|x| x.borrow_mut().level = verbosity);
+
     fern::Dispatch::new()
-        .level(verbosity)
+        .level(log::LevelFilter::Trace)
+        .filter(is_enabled)
         .format(|out, message, record| {
             out.finish(format_args!(
                 "({}) {}: {}",
@@ -45,12 +105,149 @@ pub fn setup_logging(verbosity: log::LevelFilter) {
         .chain(
             fern::Dispatch::new()
                 .level(log::LevelFilter::Warn)
-                .format(|out, message, _record| {
-                    let time = screeps::game::time();
-                    out.finish(format_args!("[{}] {}", time, message))
-                })
                 .chain(Box::new(JsNotify) as Box<dyn log::Log>),
         )
         .apply()
         .expect("expected setup_logging to only ever be called once per instance");
+
+    log::set_max_level(verbosity);
+}
+
+/// Stores a log level in `Memory.log`, for every target or just one.
+/// It takes effect from the next tick.
+pub fn set_level(target: Option<&str>, level: log::LevelFilter) {
+    let log_memory = match screeps::memory::root().dict_or_create("log") {
+        Ok(log_memory) => log_memory,
+        Err(_) => return,
+    };
+    let level_string = level.to_string().to_lowercase();
+    match target {
+        Some(target) => {
+            if let Ok(targets) = log_memory.dict_or_create("targets") {
+                targets.set(target, level_string);
+            }
+        }
+        None => log_memory.set("level", level_string),
+    }
+}
+
+/// Reloads the log levels from `Memory.log`. Call at the start of each tick.
+pub fn update_config() {
+    let config = read_config();
+    log::set_max_level(config.max_level());
+    CONFIG.with(|x| *x.borrow_mut() = config);
+}
+
+/// Sends the queued notifications as a single digest every
+/// `Memory.log.notify_interval` ticks. A message is sent at most once every
+/// `Memory.log.notify_rate_limit` ticks; repeats in between keep counting
+/// and go out with a later digest.
+pub fn flush_notifications() {
+    const DEFAULT_INTERVAL: i32 = 100;
+    const DEFAULT_RATE_LIMIT: i32 = 1500;
+
+    let notify_memory = match get_notify_memory() {
+        Some(notify_memory) => notify_memory,
+        None => return,
+    };
+    let time = screeps::game::time() as i32;
+    let interval = read_log_setting("notify_interval").unwrap_or(DEFAULT_INTERVAL);
+    let last_flush = notify_memory.i32("last_flush").ok().flatten().unwrap_or(0);
+    if time - last_flush < interval {
+        return;
+    }
+    notify_memory.set("last_flush", time);
+
+    let rate_limit = read_log_setting("notify_rate_limit").unwrap_or(DEFAULT_RATE_LIMIT);
+    let (pending, first_seen, sent) = match (
+        notify_memory.dict_or_create("pending"),
+        notify_memory.dict_or_create("first_seen"),
+        notify_memory.dict_or_create("sent"),
+    ) {
+        (Ok(pending), Ok(first_seen), Ok(sent)) => (pending, first_seen, sent),
+        _ => return,
+    };
+
+    for key in sent.keys() {
+        let sent_time = sent.i32(&key).ok().flatten().unwrap_or(0);
+        if time - sent_time >= rate_limit {
+            sent.del(&key);
+        }
+    }
+
+    let mut lines = vec![];
+    for key in pending.keys() {
+        if sent.i32(&key).ok().flatten().is_some() {
+            continue;
+        }
+        let count = pending.i32(&key).ok().flatten().unwrap_or(1);
+        let first_time = first_seen.i32(&key).ok().flatten().unwrap_or(time);
+        if count > 1 {
+            lines.push(format!("[{}] {} (x{})", first_time, key, count));
+        } else {
+            lines.push(format!("[{}] {}", first_time, key));
+        }
+        pending.del(&key);
+        first_seen.del(&key);
+        sent.set(&key, time);
+    }
+
+    if !lines.is_empty() {
+        let digest = lines.join("\n");
+        js! {
+            Game.notify(@{digest});
+        }
+    }
+}
+
+fn get_notify_memory() -> Option<MemoryReference> {
+    screeps::memory::root().dict_or_create("notify").ok()
+}
+
+fn read_log_setting(key: &str) -> Option<i32> {
+    screeps::memory::root().dict("log").ok()??.i32(key).ok()?
+}
+
+fn is_enabled(metadata: &log::Metadata<'_>) -> bool {
+    CONFIG.with(|x| metadata.level() <= x.borrow().level_for(metadata.target()))
+}
+
+fn read_config() -> LogConfig {
+    let mut config = LogConfig {
+        level: DEFAULT_LEVEL.with(|x| *x.borrow()),
+        targets: vec![],
+    };
+
+    let log_memory = match screeps::memory::root().dict("log") {
+        Ok(Some(log_memory)) => log_memory,
+        _ => return config,
+    };
+    if let Some(level) = read_level(&log_memory, "level") {
+        config.level = level;
+    }
+    if let Ok(Some(targets)) = log_memory.dict("targets") {
+        for target in targets.keys() {
+            if let Some(level) = read_level(&targets, &target) {
+                config.targets.push((normalize_target(&target), level));
+            }
+        }
+    }
+    return config;
+}
+
+fn read_level(memory: &MemoryReference, key: &str) -> Option<log::LevelFilter> {
+    let level_string = memory.string(key).ok()??;
+    return log::LevelFilter::from_str(&level_string).ok();
+}
+
+/// Allows targets to be written relative to this crate, e.g. `crate::spawn`.
+fn normalize_target(target: &str) -> String {
+    let crate_name = module_path!().split("::").next().unwrap();
+    if target == "crate" {
+        return crate_name.to_string();
+    }
+    if target.starts_with("crate::") {
+        return crate_name.to_string() + &target["crate".len()..];
+    }
+    return target.to_string();
 }
@@ -2,14 +2,45 @@ use log::*;
 use stdweb::js;
 
 mod allocator;
+mod boosts;
+mod codec;
+mod console;
+mod crash;
 mod creeps;
+mod economy;
+mod error;
+mod expansion;
+mod factory;
+mod intel;
+mod labs;
+mod lifecycle;
+mod links;
 mod logging;
+mod market;
+mod names;
+mod power;
+mod power_banks;
+mod remote;
+mod resources;
+mod rooms;
+mod segments;
 mod spawn;
+mod stats;
+mod terminal;
 
 fn main() {
-    logging::setup_logging(logging::Debug);
+    logging::setup_logging(logging::Info);
+    crash::setup_panic_hook();
     js! {
         var game_loop = @{game_loop};
+        var dispatch = @{console::dispatch};
+
+        global.bot = {};
+        @{console::command_names()}.forEach(function(name) {
+            global.bot[name] = function() {
+                return dispatch(name, Array.prototype.slice.call(arguments).map(String));
+            };
+        });
 
         module.exports.loop = function() {
             // Provide actual error traces.
@@ -31,13 +62,26 @@ fn main() {
 }
 
 fn game_loop() {
+    logging::update_config();
     debug!("loop starting! CPU: {}", screeps::game::cpu::get_used());
+    intel::game_loop();
+    remote::game_loop();
+    expansion::game_loop();
+    power_banks::game_loop();
     allocator::allocate_creeps();
+    lifecycle::game_loop(&creeps::all());
     creeps::game_loop();
+    links::game_loop();
+    terminal::game_loop();
+    boosts::game_loop();
+    labs::game_loop();
+    factory::game_loop();
+    power::game_loop();
     let mut spawn_manager = spawn::SpawnManager::new();
 
     spawn_manager.game_loop();
+    segments::game_loop();
+    logging::flush_notifications();
+    stats::game_loop();
     info!("done! cpu: {}", screeps::game::cpu::get_used());
-
-    debug!("{}", screeps::raw_memory::get());
 }
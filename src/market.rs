@@ -0,0 +1,180 @@
+//! Access to the in-game market.
+//!
+//! Trading decisions only see the `Market` trait, so they can run against
+//! an order book other than `Game.market`.
+
+use crate::resources;
+use screeps::{game::market, ResourceType, ReturnCode, RoomName};
+use stdweb::{js, unstable::TryInto};
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum OrderType {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Clone)]
+pub struct MarketOrder {
+    pub id: String,
+    pub order_type: OrderType,
+    pub resource: ResourceType,
+    /// Orders without a room, e.g. for pixels, can't be dealt by terminals.
+    pub room_name: Option<String>,
+    pub remaining_amount: u32,
+    pub price: f64,
+}
+
+pub trait Market {
+    fn get_orders(&self, resource: ResourceType, order_type: OrderType) -> Vec<MarketOrder>;
+    /// Energy the sending terminal pays to move `amount` between two rooms.
+    fn get_transaction_cost(&self, amount: u32, from: &str, to: &str) -> u32;
+    fn get_credits(&self) -> f64;
+    fn deal(&mut self, order_id: &str, amount: u32, room_name: &str) -> ReturnCode;
+}
+
+/// The real market, `Game.market`. The bindings can't filter orders and
+/// keep their fields private, so orders are read through JS.
+pub struct GameMarket;
+
+impl Market for GameMarket {
+    fn get_orders(&self, resource: ResourceType, order_type: OrderType) -> Vec<MarketOrder> {
+        let order_type_string = match order_type {
+            OrderType::Buy => "buy",
+            OrderType::Sell => "sell",
+        };
+        let orders = js! {
+            return Game.market.getAllOrders({
+                resourceType: @{resources::to_string(resource)},
+                type: @{order_type_string},
+            });
+        };
+        let ids: Vec<String> = js! { return @{&orders}.map((x) => x.id); }
+            .try_into()
+            .unwrap_or_default();
+        let room_names: Vec<String> = js! { return @{&orders}.map((x) => x.roomName || ""); }
+            .try_into()
+            .unwrap_or_default();
+        let amounts: Vec<u32> = js! { return @{&orders}.map((x) => x.remainingAmount); }
+            .try_into()
+            .unwrap_or_default();
+        let prices: Vec<f64> = js! { return @{&orders}.map((x) => x.price); }
+            .try_into()
+            .unwrap_or_default();
+        let mut result = vec![];
+        for (((id, room_name), remaining_amount), price) in
+            ids.into_iter().zip(room_names).zip(amounts).zip(prices)
+        {
+            result.push(MarketOrder {
+                id: id,
+                order_type: order_type,
+                resource: resource,
+                room_name: Some(room_name).filter(|x| !x.is_empty()),
+                remaining_amount: remaining_amount,
+                price: price,
+            });
+        }
+        return result;
+    }
+
+    fn get_transaction_cost(&self, amount: u32, from: &str, to: &str) -> u32 {
+        // The bindings want visible rooms for both ends, the game takes names.
+        let cost = js! {
+            return Game.market.calcTransactionCost(@{amount}, @{from}, @{to});
+        };
+        let cost: f64 = cost.try_into().unwrap_or(std::f64::INFINITY);
+        return cost.ceil().min(u32::MAX as f64) as u32;
+    }
+
+    fn get_credits(&self) -> f64 {
+        market::credits()
+    }
+
+    fn deal(&mut self, order_id: &str, amount: u32, room_name: &str) -> ReturnCode {
+        let room = RoomName::new(room_name)
+            .ok()
+            .and_then(screeps::game::rooms::get);
+        match room {
+            Some(room) => market::deal(order_id, amount, &room),
+            None => ReturnCode::InvalidArgs,
+        }
+    }
+}
+
+/// An order book kept in memory, so trading decisions can be tested
+/// without the game.
+#[cfg(test)]
+pub struct OrderBook {
+    pub orders: Vec<MarketOrder>,
+    pub credits: f64,
+    /// Stands in for the distance based cost as (amount, from, to).
+    pub transaction_cost: fn(u32, &str, &str) -> u32,
+    /// Deals made as (order id, amount, room name).
+    pub deals: Vec<(String, u32, String)>,
+}
+
+#[cfg(test)]
+impl Market for OrderBook {
+    fn get_orders(&self, resource: ResourceType, order_type: OrderType) -> Vec<MarketOrder> {
+        self.orders
+            .iter()
+            .filter(|x| x.resource == resource && x.order_type == order_type)
+            .cloned()
+            .collect()
+    }
+
+    fn get_transaction_cost(&self, amount: u32, from: &str, to: &str) -> u32 {
+        (self.transaction_cost)(amount, from, to)
+    }
+
+    fn get_credits(&self) -> f64 {
+        self.credits
+    }
+
+    fn deal(&mut self, order_id: &str, amount: u32, room_name: &str) -> ReturnCode {
+        let order = match self.orders.iter_mut().find(|x| x.id == order_id) {
+            Some(order) => order,
+            None => return ReturnCode::InvalidArgs,
+        };
+        if amount > order.remaining_amount {
+            return ReturnCode::NotEnough;
+        }
+        let price = order.price * amount as f64;
+        match order.order_type {
+            OrderType::Buy => self.credits += price,
+            OrderType::Sell if price > self.credits => return ReturnCode::NotEnough,
+            OrderType::Sell => self.credits -= price,
+        }
+        order.remaining_amount -= amount;
+        self.deals
+            .push((order_id.to_string(), amount, room_name.to_string()));
+        return ReturnCode::Ok;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deal_takes_from_order_and_pays() {
+        let mut market = OrderBook {
+            orders: vec![MarketOrder {
+                id: "a".to_string(),
+                order_type: OrderType::Sell,
+                resource: ResourceType::Hydrogen,
+                room_name: Some("W2N1".to_string()),
+                remaining_amount: 1000,
+                price: 0.5,
+            }],
+            credits: 1000.0,
+            transaction_cost: |_, _, _| 0,
+            deals: vec![],
+        };
+        assert_eq!(market.deal("a", 600, "W1N1"), ReturnCode::Ok);
+        assert_eq!(market.orders[0].remaining_amount, 400);
+        assert_eq!(market.credits, 700.0);
+        assert_eq!(market.deal("a", 600, "W1N1"), ReturnCode::NotEnough);
+        assert_eq!(market.deal("b", 100, "W1N1"), ReturnCode::InvalidArgs);
+        assert_eq!(market.deals.len(), 1);
+    }
+}
@@ -0,0 +1,36 @@
+//! Creep names of the form `<role code>:<home room>:<sequence>`, e.g.
+//! `h:W1N1:2s`. The sequence comes from a counter in `Memory.names` so every
+//! name is unique on the first attempt.
+
+use crate::creeps::Role;
+
+const SEQUENCE_DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// Reserves the next name. The counter advances even if the name ends up
+/// unused; the space is large enough not to care.
+pub fn next_name(role: &Role, home: &str) -> String {
+    let names_memory = screeps::memory::root().dict_or_create("names").ok();
+    let counter = names_memory
+        .as_ref()
+        .and_then(|x| x.i32("counter").ok()?)
+        .unwrap_or(0);
+    let sequence = (counter as u32).wrapping_add(1);
+    if let Some(names_memory) = names_memory {
+        names_memory.set("counter", sequence);
+    }
+    return format!("{}:{}:{}", role.code(), home, encode_sequence(sequence));
+}
+
+fn encode_sequence(mut sequence: u32) -> String {
+    let base = SEQUENCE_DIGITS.len() as u32;
+    let mut digits = vec![];
+    loop {
+        digits.push(SEQUENCE_DIGITS[(sequence % base) as usize]);
+        sequence /= base;
+        if sequence == 0 {
+            break;
+        }
+    }
+    digits.reverse();
+    return String::from_utf8(digits).expect("expected sequence digits to be ascii");
+}
@@ -0,0 +1,86 @@
+//! Burning power in power spawns.
+//!
+//! A power spawn only runs while its room has surplus energy, turning one
+//! power and 50 energy into GPL progress each tick. Haulers keep it stocked
+//! from the terminal or storage.
+
+use crate::economy::{self, EnergyLevel};
+use crate::labs;
+use log::*;
+use screeps::{find, prelude::*, RawObjectId, ResourceType, ReturnCode, StructurePowerSpawn};
+
+/// POWER_SPAWN_ENERGY_RATIO
+const ENERGY_PER_POWER: u32 = 50;
+/// Power and energy are topped up once the power spawn is below these.
+const POWER_REFILL: u32 = 50;
+const ENERGY_REFILL: u32 = 2500;
+
+pub fn game_loop() {
+    for room in screeps::game::rooms::values() {
+        if !room.controller().map_or(false, |x| x.my()) {
+            continue;
+        }
+        if economy::get_energy_level(&room) != EnergyLevel::Surplus {
+            continue;
+        }
+        let power_spawn = match get_power_spawn(&room) {
+            Some(power_spawn) => power_spawn,
+            None => continue,
+        };
+        let can_process = power_spawn.store_of(ResourceType::Power) > 0
+            && power_spawn.store_of(ResourceType::Energy) >= ENERGY_PER_POWER;
+        if can_process {
+            let return_code = power_spawn.process_power();
+            if return_code != ReturnCode::Ok {
+                warn!(
+                    "power spawn {:?} failed to process power: {:?}",
+                    power_spawn.pos(),
+                    return_code
+                );
+            }
+        }
+        if !get_hauler_jobs(&room).is_empty() {
+            labs::request_hauler(&room.name().to_string());
+        }
+    }
+}
+
+/// Hauler jobs to stock a room's power spawn as (from, resource, to).
+pub fn get_hauler_jobs(room: &screeps::Room) -> Vec<(RawObjectId, ResourceType, RawObjectId)> {
+    let mut result = vec![];
+    if economy::get_energy_level(room) != EnergyLevel::Surplus {
+        return result;
+    }
+    let power_spawn = match get_power_spawn(room) {
+        Some(power_spawn) => power_spawn,
+        None => return result,
+    };
+    let has_power = power_spawn.store_of(ResourceType::Power) > 0
+        || labs::get_source_of(room, ResourceType::Power).is_some();
+    if !has_power {
+        return result;
+    }
+    for (resource, refill) in [
+        (ResourceType::Power, POWER_REFILL),
+        (ResourceType::Energy, ENERGY_REFILL),
+    ]
+    .iter()
+    {
+        if power_spawn.store_of(*resource) >= *refill {
+            continue;
+        }
+        if let Some(source_id) = labs::get_source_of(room, *resource) {
+            result.push((source_id, *resource, power_spawn.untyped_id()));
+        }
+    }
+    return result;
+}
+
+fn get_power_spawn(room: &screeps::Room) -> Option<StructurePowerSpawn> {
+    room.find(find::STRUCTURES)
+        .into_iter()
+        .find_map(|x| match x {
+            screeps::Structure::PowerSpawn(power_spawn) if power_spawn.my() => Some(power_spawn),
+            _ => None,
+        })
+}
@@ -0,0 +1,499 @@
+//! Harvesting power banks in highway rooms.
+//!
+//! One operation runs at a time and is tracked in `Memory.power_bank`. A
+//! bank from room intel is taken on when a nearby room can break it within
+//! one creep lifetime and before it decays. Pairs of attackers and healers
+//! wear it down, carriers are queued to arrive about when it breaks, and
+//! they bring the dropped power home.
+
+use crate::allocator;
+use crate::creeps::{self, Creep, Role, Task};
+use crate::economy::{self, EnergyLevel};
+use crate::intel::{self, PowerBankIntel};
+use crate::spawn::{self, SpawnRequest};
+use log::*;
+use screeps::{
+    find, prelude::*, Attackable, Part, Position, Resource, ResourceType, RoomName, Structure,
+    StructurePowerBank, Terrain,
+};
+
+const SELECT_INTERVAL: u32 = 100;
+/// Parents need RCL8 extensions to spawn pairs that can break a fresh bank
+/// within one creep lifetime.
+const MINIMUM_PARENT_CAPACITY: u32 = 12900;
+/// Banks further than this many rooms from the parent are ignored.
+const MAX_DISTANCE: u32 = 4;
+/// Banks with less power aren't worth the trip.
+const MIN_POWER: u32 = 1000;
+/// Banks seen longer ago than this may already be taken.
+const MAX_INTEL_AGE: u32 = 500;
+const PAIRS: u32 = 3;
+/// Rough ticks to cross a room, for travel estimates.
+const TICKS_PER_ROOM: u32 = 50;
+/// Dropped power is collected for this long after the bank breaks.
+const COLLECT_TIMEOUT: u32 = 2000;
+/// Creeps waiting for their partner or the bank to break stay this far out.
+const WAITING_RANGE: u32 = 3;
+/// CREEP_LIFE_TIME
+const CREEP_LIFE_TIME: u32 = 1500;
+/// CREEP_SPAWN_TIME
+const SPAWN_TIME_PER_PART: u32 = 3;
+/// CARRY_CAPACITY
+const CARRY_CAPACITY: u32 = 50;
+/// ATTACK_POWER
+pub const ATTACK_POWER: u32 = 30;
+/// HEAL_POWER
+pub const HEAL_POWER: u32 = 12;
+/// POWER_BANK_HIT_BACK, as a percentage of the damage dealt.
+pub const HIT_BACK_PERCENT: u32 = 50;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Status {
+    /// Attackers and healers are breaking the bank.
+    Attacking,
+    /// The bank is broken, carriers are taking the power home.
+    Collecting,
+}
+
+const STATUS_STRINGS: [(Status, &'static str); 2] = [
+    (Status::Attacking, "attacking"),
+    (Status::Collecting, "collecting"),
+];
+
+#[derive(Debug, Clone)]
+pub struct Operation {
+    pub room: String,
+    pub parent: String,
+    pub status: Status,
+    pub since: u32,
+    pub power: u32,
+    pub decays_at: u32,
+}
+
+pub fn game_loop() {
+    let time = screeps::game::time();
+    match get_operation() {
+        Some(operation) => update(operation, time),
+        None => {
+            if time % SELECT_INTERVAL == 0 {
+                start_operation(time);
+            }
+        }
+    }
+}
+
+/// The operation in progress, if any.
+pub fn get_operation() -> Option<Operation> {
+    let memory = screeps::memory::root().dict("power_bank").ok()??;
+    let status_string = memory.string("status").ok()??;
+    let status = STATUS_STRINGS
+        .iter()
+        .find(|(_, x)| *x == status_string)
+        .map(|(status, _)| status.clone())?;
+    Some(Operation {
+        room: memory.string("room").ok()??,
+        parent: memory.string("parent").ok()??,
+        status: status,
+        since: memory.i32("since").ok()?.unwrap_or(0) as u32,
+        power: memory.i32("power").ok()?.unwrap_or(0) as u32,
+        decays_at: memory.i32("decays_at").ok()?.unwrap_or(0) as u32,
+    })
+}
+
+/// Highway rooms have a coordinate divisible by 10, e.g. W10N3.
+pub fn is_highway(room_name: &str) -> bool {
+    room_name
+        .split(|x: char| x.is_ascii_alphabetic())
+        .filter(|x| !x.is_empty())
+        .any(|x| x.parse::<u32>().map_or(false, |x| x % 10 == 0))
+}
+
+/// Whether pairs dealing `damage` each per tick can break the bank within
+/// one creep lifetime and before it decays, given the time to spawn them
+/// and travel `distance` rooms.
+pub fn is_worth_harvesting(
+    bank: &PowerBankIntel,
+    distance: u32,
+    damage: u32,
+    spawn_time: u32,
+    time: u32,
+) -> bool {
+    if bank.power < MIN_POWER || damage == 0 {
+        return false;
+    }
+    let travel_time = distance * TICKS_PER_ROOM;
+    let break_time = bank.hits / (damage * PAIRS);
+    return travel_time + break_time < CREEP_LIFE_TIME
+        && time + spawn_time + travel_time + break_time < bank.decays_at;
+}
+
+/// The room of the operation the creep was first allocated to.
+pub fn get_target_room(creep: &Creep) -> Option<String> {
+    if let Ok(Some(room_name)) = creep.memory().string("power_bank") {
+        return Some(room_name);
+    }
+    let room_name = get_operation()?.room;
+    creep.memory().set("power_bank", room_name.as_str());
+    return Some(room_name);
+}
+
+pub fn get_power_bank(room: &screeps::Room) -> Option<StructurePowerBank> {
+    room.find(find::STRUCTURES)
+        .into_iter()
+        .find_map(|x| match x {
+            Structure::PowerBank(power_bank) => Some(power_bank),
+            _ => None,
+        })
+}
+
+pub fn get_dropped_power(room: &screeps::Room) -> Vec<Resource> {
+    room.find(find::DROPPED_RESOURCES)
+        .into_iter()
+        .filter(|x| x.resource_type() == ResourceType::Power)
+        .collect()
+}
+
+/// The attacker a healer looks after. Healers without a living partner
+/// take the first attacker no other healer has.
+pub fn get_partner(healer: &Creep) -> Option<Creep> {
+    if let Ok(Some(partner_name)) = healer.memory().string("partner") {
+        if let Some(partner) = creeps::all().into_iter().find(|x| x.name() == partner_name) {
+            return Some(partner);
+        }
+    }
+    let all_creeps = creeps::all();
+    let taken: Vec<String> = all_creeps
+        .iter()
+        .filter(|x| x.role == Role::PowerHealer)
+        .filter_map(|x| x.memory().string("partner").ok()?)
+        .collect();
+    let partner = all_creeps.into_iter().find(|x| {
+        x.role == Role::PowerAttacker && !x.is_spawning() && !taken.contains(&x.name())
+    })?;
+    healer.memory().set("partner", partner.name().as_str());
+    return Some(partner);
+}
+
+/// The healer looking after an attacker, if it has one.
+pub fn get_healer(attacker: &Creep) -> Option<Creep> {
+    creeps::all().into_iter().find(|x| {
+        x.role == Role::PowerHealer
+            && x.memory().string("partner").ok().flatten() == Some(attacker.name())
+    })
+}
+
+/// An open tile at `WAITING_RANGE` from the bank, spread out by `index`.
+pub fn get_waiting_position(
+    room: &screeps::Room,
+    power_bank: Position,
+    index: usize,
+) -> Option<Position> {
+    let terrain = room.get_terrain();
+    let range = WAITING_RANGE as i32;
+    let mut positions = vec![];
+    for dx in -range..=range {
+        for dy in -range..=range {
+            if dx.abs().max(dy.abs()) != range {
+                continue;
+            }
+            let x = power_bank.x() as i32 + dx;
+            let y = power_bank.y() as i32 + dy;
+            if x < 1 || x > 48 || y < 1 || y > 48 {
+                continue;
+            }
+            if terrain.get(x as u32, y as u32) != Terrain::Wall {
+                positions.push(Position::new(x as u32, y as u32, room.name()));
+            }
+        }
+    }
+    if positions.is_empty() {
+        return None;
+    }
+    return Some(positions[index % positions.len()]);
+}
+
+/// Sends a creep whose work is done back to a home spawn to be recycled.
+pub fn retire(creep: &Creep) {
+    let spawn = creep
+        .get_home()
+        .and_then(|x| RoomName::new(&x).ok())
+        .and_then(|x| screeps::game::rooms::get(x))
+        .and_then(|x| x.find(find::MY_SPAWNS).into_iter().next());
+    if let Some(spawn) = spawn {
+        info!("{}: power bank done, recycling", creep.name());
+        creep.push_task(Task::Recycle(spawn.untyped_id()));
+    }
+}
+
+fn update(mut operation: Operation, time: u32) {
+    let room = RoomName::new(&operation.room)
+        .ok()
+        .and_then(|x| screeps::game::rooms::get(x));
+
+    match operation.status {
+        Status::Attacking => {
+            let power_bank = room.as_ref().and_then(get_power_bank);
+            if room.is_some() && power_bank.is_none() {
+                info!("power bank in {} broken, collecting", operation.room);
+                operation.status = Status::Collecting;
+                operation.since = time;
+                save(Some(&operation));
+                return;
+            }
+            if time >= operation.decays_at {
+                fail(&operation, "decayed before breaking");
+                return;
+            }
+            request_creeps(&operation, &Role::PowerHealer, PAIRS as usize);
+            request_creeps(&operation, &Role::PowerAttacker, PAIRS as usize);
+            if let Some(power_bank) = power_bank {
+                // Carriers set off once the bank breaks before they could arrive.
+                let damage = get_current_damage(&operation.room);
+                if power_bank.hits() <= damage * get_carrier_lead_time(&operation) {
+                    request_creeps(
+                        &operation,
+                        &Role::PowerCarrier,
+                        get_carrier_count(&operation),
+                    );
+                }
+            }
+        }
+        Status::Collecting => {
+            let has_power = room
+                .as_ref()
+                .map_or(true, |x| !get_dropped_power(x).is_empty());
+            if !has_power {
+                info!("power from {} collected", operation.room);
+                save(None);
+                return;
+            }
+            if time.saturating_sub(operation.since) > COLLECT_TIMEOUT {
+                fail(&operation, "collection timed out");
+                return;
+            }
+            request_creeps(
+                &operation,
+                &Role::PowerCarrier,
+                get_carrier_count(&operation),
+            );
+        }
+    }
+}
+
+/// Queues creeps of a role in the parent room until `count` are alive or
+/// waiting to spawn.
+fn request_creeps(operation: &Operation, role: &Role, count: usize) {
+    let alive = creeps::all().iter().filter(|x| x.role == *role).count();
+    let queued = spawn::get_queue(&operation.parent)
+        .iter()
+        .filter(|x| x.role == *role)
+        .count();
+    if alive + queued < count {
+        debug!("requesting {} for {}", role.to_string(), operation.room);
+        spawn::queue_request(&operation.parent, SpawnRequest::new(role.clone()));
+    }
+}
+
+/// Damage per tick of the attackers at the bank.
+fn get_current_damage(room_name: &str) -> u32 {
+    let attack_parts = creeps::all()
+        .iter()
+        .filter(|x| x.role == Role::PowerAttacker && x.room_name().to_string() == room_name)
+        .map(|x| x.body().iter().filter(|x| **x == Part::Attack).count() as u32)
+        .sum::<u32>();
+    return attack_parts * ATTACK_POWER;
+}
+
+fn get_carrier_lead_time(operation: &Operation) -> u32 {
+    let capacity = get_parent_capacity(operation);
+    let body = allocator::get_body(&Role::PowerCarrier, capacity);
+    let spawn_time = body.len() as u32 * SPAWN_TIME_PER_PART * get_carrier_count(operation) as u32;
+    return spawn_time + get_distance(&operation.parent, &operation.room) * TICKS_PER_ROOM;
+}
+
+fn get_carrier_count(operation: &Operation) -> usize {
+    let body = allocator::get_body(&Role::PowerCarrier, get_parent_capacity(operation));
+    let carry_capacity =
+        (body.iter().filter(|x| **x == Part::Carry).count() as u32 * CARRY_CAPACITY).max(1);
+    return ((operation.power + carry_capacity - 1) / carry_capacity) as usize;
+}
+
+fn get_parent_capacity(operation: &Operation) -> u32 {
+    RoomName::new(&operation.parent)
+        .ok()
+        .and_then(|x| screeps::game::rooms::get(x))
+        .map_or(MINIMUM_PARENT_CAPACITY, |x| spawn::get_spawn_capacity(&x))
+}
+
+fn get_distance(from: &str, to: &str) -> u32 {
+    match (RoomName::new(from), RoomName::new(to)) {
+        (Ok(from), Ok(to)) => screeps::game::map::get_room_linear_distance(from, to, false),
+        _ => u32::MAX,
+    }
+}
+
+fn start_operation(time: u32) {
+    let parents: Vec<screeps::Room> = screeps::game::rooms::values()
+        .into_iter()
+        .filter(|x| x.controller().map_or(false, |x| x.my()))
+        .filter(|x| x.energy_capacity_available() >= MINIMUM_PARENT_CAPACITY)
+        .filter(|x| economy::get_energy_level(x) >= EnergyLevel::Normal)
+        .filter(|x| !x.find(find::MY_SPAWNS).is_empty())
+        .collect();
+    if parents.is_empty() {
+        return;
+    }
+
+    let mut best: Option<(u32, String, String, PowerBankIntel)> = None;
+    for room_intel in intel::all() {
+        let power_bank = match &room_intel.power_bank {
+            Some(power_bank) => power_bank,
+            None => continue,
+        };
+        if !is_highway(&room_intel.name)
+            || time.saturating_sub(room_intel.last_seen) > MAX_INTEL_AGE
+        {
+            continue;
+        }
+        let parent = parents
+            .iter()
+            .map(|x| (get_distance(&x.name().to_string(), &room_intel.name), x))
+            .min_by_key(|(distance, _)| *distance);
+        let (distance, parent) = match parent {
+            Some((distance, parent)) if distance <= MAX_DISTANCE => (distance, parent),
+            _ => continue,
+        };
+        let capacity = parent.energy_capacity_available();
+        let damage = allocator::get_body(&Role::PowerAttacker, capacity)
+            .iter()
+            .filter(|x| **x == Part::Attack)
+            .count() as u32
+            * ATTACK_POWER;
+        let pair_parts = allocator::get_body(&Role::PowerAttacker, capacity).len()
+            + allocator::get_body(&Role::PowerHealer, capacity).len();
+        let spawns = parent.find(find::MY_SPAWNS).len() as u32;
+        let spawn_time = pair_parts as u32 * SPAWN_TIME_PER_PART * PAIRS / spawns;
+        if !is_worth_harvesting(power_bank, distance, damage, spawn_time, time) {
+            continue;
+        }
+        let score = power_bank.power / (distance + 1);
+        if best
+            .as_ref()
+            .map_or(true, |(best_score, _, _, _)| score > *best_score)
+        {
+            best = Some((
+                score,
+                room_intel.name.clone(),
+                parent.name().to_string(),
+                power_bank.clone(),
+            ));
+        }
+    }
+
+    if let Some((_, room, parent, power_bank)) = best {
+        info!(
+            "harvesting power bank in {} from {} ({} power)",
+            room, parent, power_bank.power
+        );
+        save(Some(&Operation {
+            room: room,
+            parent: parent,
+            status: Status::Attacking,
+            since: time,
+            power: power_bank.power,
+            decays_at: power_bank.decays_at,
+        }));
+    }
+}
+
+fn fail(operation: &Operation, reason: &str) {
+    warn!("power bank in {} abandoned: {}", operation.room, reason);
+    save(None);
+}
+
+fn save(operation: Option<&Operation>) {
+    let operation = match operation {
+        Some(operation) => operation,
+        None => {
+            screeps::memory::root().del("power_bank");
+            return;
+        }
+    };
+    let memory = match screeps::memory::root().dict_or_create("power_bank") {
+        Ok(memory) => memory,
+        Err(_) => return,
+    };
+    let status_string = STATUS_STRINGS
+        .iter()
+        .find(|(status, _)| *status == operation.status)
+        .map(|(_, x)| *x)
+        .unwrap_or("attacking");
+    memory.set("room", operation.room.as_str());
+    memory.set("parent", operation.parent.as_str());
+    memory.set("status", status_string);
+    memory.set("since", operation.since);
+    memory.set("power", operation.power);
+    memory.set("decays_at", operation.decays_at);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh bank, as spawned in highway rooms.
+    fn get_bank(power: u32, decays_at: u32) -> PowerBankIntel {
+        PowerBankIntel {
+            position: Position::new(25, 25, RoomName::new("W10N5").unwrap()),
+            power: power,
+            hits: 2000000,
+            decays_at: decays_at,
+        }
+    }
+
+    #[test]
+    fn highways_have_a_coordinate_divisible_by_ten() {
+        assert!(is_highway("W10N3"));
+        assert!(is_highway("E3S20"));
+        assert!(is_highway("W0N0"));
+        assert!(!is_highway("W11N3"));
+        assert!(!is_highway("E1S9"));
+        assert!(!is_highway("sim"));
+    }
+
+    #[test]
+    fn fresh_bank_nearby_is_worth_harvesting() {
+        // Pairs of 20 attack parts break a fresh bank in 1111 ticks.
+        assert!(is_worth_harvesting(
+            &get_bank(3000, 5000),
+            2,
+            20 * ATTACK_POWER,
+            150,
+            0
+        ));
+    }
+
+    #[test]
+    fn small_banks_are_not_worth_harvesting() {
+        assert!(!is_worth_harvesting(
+            &get_bank(MIN_POWER - 1, 5000),
+            2,
+            20 * ATTACK_POWER,
+            150,
+            0
+        ));
+    }
+
+    #[test]
+    fn banks_that_outlast_the_creeps_are_not_worth_harvesting() {
+        let bank = get_bank(3000, 5000);
+        assert!(!is_worth_harvesting(&bank, 2, 0, 150, 0));
+        assert!(!is_worth_harvesting(&bank, 2, 10 * ATTACK_POWER, 150, 0));
+        assert!(!is_worth_harvesting(&bank, 10, 20 * ATTACK_POWER, 150, 0));
+    }
+
+    #[test]
+    fn banks_about_to_decay_are_not_worth_harvesting() {
+        let bank = get_bank(3000, 5000);
+        assert!(!is_worth_harvesting(&bank, 2, 20 * ATTACK_POWER, 150, 4000));
+    }
+}
@@ -0,0 +1,473 @@
+//! Remote mining in unowned rooms next to our own.
+//!
+//! Neighbors of each owned room are recorded as candidates in `Memory.remote`
+//! and activated closest first. Activated rooms get a reserver, a miner per
+//! source sitting on a container and haulers carrying energy home. A room is
+//! abandoned when it turns hostile or someone else takes it, and reconsidered
+//! once things have had time to calm down.
+
+use crate::creeps::{self, Creep, Role, Task};
+use crate::intel;
+use log::*;
+use screeps::{find, prelude::*, Part, Position, RoomName, StructureType};
+
+const SELECT_INTERVAL: u32 = 100;
+const MAX_REMOTE_ROOMS_PER_HOME: usize = 2;
+/// Sources further than this from the home spawn aren't worth hauling from.
+const MAX_DISTANCE: u32 = 120;
+const ABANDON_TIME: u32 = 1500;
+/// Home rooms need to afford a reserver before running remotes.
+const MINIMUM_HOME_CAPACITY: u32 = 650;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Status {
+    Candidate,
+    Active,
+    Abandoned,
+    Rejected,
+}
+
+const STATUS_STRINGS: [(Status, &'static str); 4] = [
+    (Status::Candidate, "candidate"),
+    (Status::Active, "active"),
+    (Status::Abandoned, "abandoned"),
+    (Status::Rejected, "rejected"),
+];
+
+#[derive(Debug, Clone)]
+pub struct RemoteRoom {
+    pub name: String,
+    pub home: String,
+    pub status: Status,
+    /// Average path length from the home spawn to the sources, once seen.
+    pub distance: Option<u32>,
+    pub sources: u32,
+}
+
+pub fn game_loop() {
+    let time = screeps::game::time();
+    for mut remote_room in get_remote_rooms() {
+        if update_status(&mut remote_room, time) {
+            save(&remote_room);
+        }
+    }
+
+    if time % SELECT_INTERVAL == 0 {
+        add_candidates();
+        select_rooms();
+    }
+}
+
+/// Remote rooms currently being mined.
+pub fn get_active_rooms() -> Vec<RemoteRoom> {
+    get_remote_rooms()
+        .into_iter()
+        .filter(|x| x.status == Status::Active)
+        .collect()
+}
+
+pub fn is_active(room_name: &str) -> bool {
+    get_active_rooms().iter().any(|x| x.name == room_name)
+}
+
+fn update_status(remote_room: &mut RemoteRoom, time: u32) -> bool {
+    let since = get_status_time(&remote_room.name);
+    if remote_room.status == Status::Abandoned && time.saturating_sub(since) > ABANDON_TIME {
+        info!("reconsidering remote room {}", remote_room.name);
+        remote_room.status = Status::Candidate;
+        return true;
+    }
+
+    let room = match get_room(&remote_room.name) {
+        Some(room) => room,
+        None => return false,
+    };
+    if remote_room.status != Status::Active {
+        return false;
+    }
+
+    if is_hostile(&room) {
+        warn!("abandoning remote room {}: hostile", remote_room.name);
+        remote_room.status = Status::Abandoned;
+        return true;
+    }
+    if !is_unclaimed(&room) {
+        warn!("abandoning remote room {}: claimed", remote_room.name);
+        remote_room.status = Status::Abandoned;
+        return true;
+    }
+    if remote_room.distance.is_none() {
+        evaluate_visible(remote_room, &room);
+        if remote_room.status == Status::Active {
+            place_infrastructure(remote_room, &room);
+        }
+        return true;
+    }
+    return false;
+}
+
+fn add_candidates() {
+    let known: Vec<String> = get_remote_rooms().into_iter().map(|x| x.name).collect();
+    for home in get_home_rooms() {
+        let home_name = home.name();
+        for neighbor in screeps::game::map::describe_exits(home_name).values() {
+            let neighbor_name = neighbor.to_string();
+            if known.contains(&neighbor_name) {
+                continue;
+            }
+            if let Some(room) = get_room(&neighbor_name) {
+                if room.controller().map_or(false, |x| x.my()) {
+                    continue;
+                }
+            }
+            debug!("remote candidate {} for {}", neighbor_name, home_name);
+            save(&RemoteRoom {
+                name: neighbor_name,
+                home: home_name.to_string(),
+                status: Status::Candidate,
+                distance: None,
+                sources: 0,
+            });
+        }
+    }
+}
+
+/// Activates the closest candidates, judged by what is visible now or was
+/// last recorded by scouts. Rooms nobody has seen yet are tried last; they
+/// are evaluated as soon as the reserver gives vision.
+fn select_rooms() {
+    let remote_rooms = get_remote_rooms();
+    for home in get_home_rooms() {
+        let home_name = home.name().to_string();
+        let active_count = remote_rooms
+            .iter()
+            .filter(|x| x.home == home_name && x.status == Status::Active)
+            .count();
+        let mut candidates: Vec<RemoteRoom> = remote_rooms
+            .iter()
+            .filter(|x| x.home == home_name && x.status == Status::Candidate)
+            .cloned()
+            .collect();
+        for candidate in &mut candidates {
+            if let Some(room) = get_room(&candidate.name) {
+                evaluate_visible(candidate, &room);
+            } else if let Some(room_intel) = intel::get(&candidate.name) {
+                evaluate_intel(candidate, &room_intel);
+            }
+        }
+        candidates.retain(|x| x.status == Status::Candidate);
+        candidates.sort_by_key(|x| x.distance.unwrap_or(MAX_DISTANCE));
+
+        for mut candidate in candidates
+            .into_iter()
+            .take(MAX_REMOTE_ROOMS_PER_HOME.saturating_sub(active_count))
+        {
+            info!(
+                "activating remote room {} for {}",
+                candidate.name, home_name
+            );
+            candidate.status = Status::Active;
+            if let Some(room) = get_room(&candidate.name) {
+                place_infrastructure(&candidate, &room);
+            }
+            save(&candidate);
+        }
+    }
+}
+
+fn evaluate_visible(remote_room: &mut RemoteRoom, room: &screeps::Room) {
+    let sources: Vec<Position> = room.find(find::SOURCES).iter().map(|x| x.pos()).collect();
+    evaluate(remote_room, &sources, room.controller().is_some());
+}
+
+/// Judges a room from scouting reports. Rooms someone else owns or reserves
+/// are abandoned and reconsidered later, like rooms lost while active.
+fn evaluate_intel(remote_room: &mut RemoteRoom, room_intel: &intel::RoomIntel) {
+    let username = get_my_username();
+    let is_taken = room_intel.controller.as_ref().map_or(false, |x| {
+        x.owner.is_some()
+            || x.reservation
+                .as_ref()
+                .map_or(false, |(reserver, _)| Some(reserver) != username.as_ref())
+    });
+    if is_taken || room_intel.hostile_structures > 0 {
+        info!("abandoning remote room {}: taken", remote_room.name);
+        remote_room.status = Status::Abandoned;
+        save(remote_room);
+        return;
+    }
+    evaluate(
+        remote_room,
+        &room_intel.sources,
+        room_intel.controller.is_some(),
+    );
+}
+
+/// Measures a room, rejecting it if it can't be mined profitably.
+fn evaluate(remote_room: &mut RemoteRoom, sources: &[Position], has_controller: bool) {
+    remote_room.sources = sources.len() as u32;
+    if !has_controller || sources.is_empty() {
+        info!(
+            "rejecting remote room {}: nothing to mine",
+            remote_room.name
+        );
+        remote_room.status = Status::Rejected;
+        save(remote_room);
+        return;
+    }
+
+    let spawn_position = match get_home_spawn_position(&remote_room.home) {
+        Some(position) => position,
+        None => return,
+    };
+    let total_distance: u32 = sources
+        .iter()
+        .map(|x| get_path(spawn_position, *x).len() as u32)
+        .sum();
+    let distance = total_distance / sources.len() as u32;
+    remote_room.distance = Some(distance);
+    if distance > MAX_DISTANCE {
+        info!(
+            "rejecting remote room {}: {} tiles away",
+            remote_room.name, distance
+        );
+        remote_room.status = Status::Rejected;
+    }
+    save(remote_room);
+}
+
+/// Places a container beside each source and a road back to the home spawn.
+fn place_infrastructure(remote_room: &RemoteRoom, room: &screeps::Room) {
+    let spawn_position = match get_home_spawn_position(&remote_room.home) {
+        Some(position) => position,
+        None => return,
+    };
+    for source in room.find(find::SOURCES) {
+        let path = get_path(spawn_position, source.pos());
+        for position in &path {
+            if let Some(path_room) = screeps::game::rooms::get(position.room_name()) {
+                path_room.create_construction_site(position, StructureType::Road);
+            }
+        }
+        if get_source_container(&source).is_none() {
+            if let Some(container_position) = path.last() {
+                room.create_construction_site(container_position, StructureType::Container);
+            }
+        }
+    }
+}
+
+/// The container, or container site, beside a remote source.
+pub fn get_source_container(source: &screeps::Source) -> Option<screeps::RawObjectId> {
+    let room = source.room();
+    let container = room
+        .find(find::STRUCTURES)
+        .into_iter()
+        .find(|x| x.structure_type() == StructureType::Container && source.pos().is_near_to(x));
+    if let Some(container) = container {
+        return Some(container.untyped_id());
+    }
+    let site = room
+        .find(find::MY_CONSTRUCTION_SITES)
+        .into_iter()
+        .find(|x| x.structure_type() == StructureType::Container && source.pos().is_near_to(x));
+    return site.map(|x| x.untyped_id());
+}
+
+fn is_hostile(room: &screeps::Room) -> bool {
+    let has_hostile_creeps = room.find(find::HOSTILE_CREEPS).iter().any(|x| {
+        x.body()
+            .iter()
+            .any(|x| x.part == Part::Attack || x.part == Part::RangedAttack)
+    });
+    let has_hostile_structures = !room.find(find::HOSTILE_STRUCTURES).is_empty();
+    return has_hostile_creeps || has_hostile_structures;
+}
+
+fn is_unclaimed(room: &screeps::Room) -> bool {
+    let controller = match room.controller() {
+        Some(controller) => controller,
+        None => return false,
+    };
+    if controller.owner_name().is_some() {
+        return false;
+    }
+    if let Some(reservation) = controller.reservation() {
+        return Some(reservation.username) == get_my_username();
+    }
+    return true;
+}
+
+pub fn get_my_username() -> Option<String> {
+    screeps::game::rooms::values()
+        .into_iter()
+        .filter_map(|x| x.controller())
+        .find(|x| x.my())?
+        .owner_name()
+}
+
+fn get_home_rooms() -> Vec<screeps::Room> {
+    screeps::game::rooms::values()
+        .into_iter()
+        .filter(|x| match x.controller() {
+            Some(controller) => controller.my(),
+            None => false,
+        })
+        .filter(|x| x.energy_capacity_available() >= MINIMUM_HOME_CAPACITY)
+        .collect()
+}
+
+fn get_room(room_name: &str) -> Option<screeps::Room> {
+    screeps::game::rooms::get(RoomName::new(room_name).ok()?)
+}
+
+pub fn get_home_spawn_position(home: &str) -> Option<Position> {
+    Some(get_room(home)?.find(find::MY_SPAWNS).first()?.pos())
+}
+
+fn get_path(from: Position, to: Position) -> Vec<Position> {
+    screeps::pathfinder::search(&from, &to, 1, screeps::pathfinder::SearchOptions::default())
+        .load_local_path()
+}
+
+fn get_remote_rooms() -> Vec<RemoteRoom> {
+    let remote_memory = match screeps::memory::root().dict_or_create("remote") {
+        Ok(remote_memory) => remote_memory,
+        Err(_) => return vec![],
+    };
+    let mut result = vec![];
+    for name in remote_memory.keys() {
+        let room_memory = match remote_memory.dict(&name) {
+            Ok(Some(room_memory)) => room_memory,
+            _ => continue,
+        };
+        let status_string = room_memory.string("status").ok().flatten();
+        let status = STATUS_STRINGS
+            .iter()
+            .find(|(_, x)| Some(x.to_string()) == status_string)
+            .map(|(status, _)| status.clone())
+            .unwrap_or(Status::Candidate);
+        result.push(RemoteRoom {
+            name: name,
+            home: room_memory
+                .string("home")
+                .ok()
+                .flatten()
+                .unwrap_or_default(),
+            status: status,
+            distance: room_memory.i32("distance").ok().flatten().map(|x| x as u32),
+            sources: room_memory.i32("sources").ok().flatten().unwrap_or(0) as u32,
+        });
+    }
+    return result;
+}
+
+fn save(remote_room: &RemoteRoom) {
+    let room_memory = match screeps::memory::root()
+        .dict_or_create("remote")
+        .and_then(|x| x.dict_or_create(&remote_room.name))
+    {
+        Ok(room_memory) => room_memory,
+        Err(_) => return,
+    };
+    let status_string = STATUS_STRINGS
+        .iter()
+        .find(|(status, _)| *status == remote_room.status)
+        .map(|(_, x)| *x)
+        .unwrap_or("candidate");
+    if room_memory.string("status").ok().flatten().as_deref() != Some(status_string) {
+        room_memory.set("since", screeps::game::time());
+    }
+    room_memory.set("home", remote_room.home.as_str());
+    room_memory.set("status", status_string);
+    room_memory.set("sources", remote_room.sources);
+    match remote_room.distance {
+        Some(distance) => room_memory.set("distance", distance),
+        None => room_memory.del("distance"),
+    }
+}
+
+fn get_status_time(room_name: &str) -> u32 {
+    screeps::memory::root()
+        .dict("remote")
+        .ok()
+        .flatten()
+        .and_then(|x| x.dict(room_name).ok()?)
+        .and_then(|x| x.i32("since").ok()?)
+        .unwrap_or(0) as u32
+}
+
+/// The remote room a creep works in.
+pub fn get_assigned_room(creep: &Creep) -> Option<String> {
+    creep.memory().string("remote").ok()?
+}
+
+pub fn assign_room(creep: &Creep, room_name: &str) {
+    debug!("{}: assigned to remote room {}", creep.name(), room_name);
+    creep.memory().set("remote", room_name);
+}
+
+pub fn count_assigned(role: &Role, room_name: &str) -> usize {
+    creeps::all()
+        .iter()
+        .filter(|x| x.role == *role && get_assigned_room(x).as_deref() == Some(room_name))
+        .count()
+}
+
+/// Sends creeps of abandoned rooms home to be recycled. Returns true if the
+/// creep is retiring.
+pub fn retire_if_inactive(creep: &Creep) -> bool {
+    let room_name = match get_assigned_room(creep) {
+        Some(room_name) => room_name,
+        None => return false,
+    };
+    if is_active(&room_name) {
+        return false;
+    }
+    if creep.get_tasks().is_empty() {
+        let home = creep.get_home().unwrap_or_default();
+        match get_room(&home).and_then(|x| x.find(find::MY_SPAWNS).into_iter().next()) {
+            Some(spawn) => {
+                info!("{}: leaving remote room {}", creep.name(), room_name);
+                creep.push_task(Task::Recycle(spawn.untyped_id()));
+            }
+            None => warn!("{}: no home spawn to retire to", creep.name()),
+        }
+    }
+    return true;
+}
+
+/// Walks the creep into its assigned room while it has no vision there.
+/// Returns true if the creep is travelling.
+pub fn travel_to_assigned_room(creep: &Creep) -> bool {
+    let room_name = match get_assigned_room(creep) {
+        Some(room_name) => room_name,
+        None => return false,
+    };
+    if creep.room_name().to_string() == room_name || get_room(&room_name).is_some() {
+        return false;
+    }
+    if creep.get_tasks().is_empty() {
+        if let Ok(room_name) = RoomName::new(&room_name) {
+            creep.push_task(Task::MoveToRoom(Position::new(25, 25, room_name)));
+        }
+    }
+    return true;
+}
+
+pub fn get_visible_room(room_name: &str) -> Option<screeps::Room> {
+    get_room(room_name)
+}
+
+/// Built containers beside the sources of a room.
+pub fn get_source_containers(room: &screeps::Room) -> Vec<screeps::StructureContainer> {
+    let sources = room.find(find::SOURCES);
+    room.find(find::STRUCTURES)
+        .into_iter()
+        .filter_map(|x| match x {
+            screeps::Structure::Container(container) => Some(container),
+            _ => None,
+        })
+        .filter(|x| sources.iter().any(|source| source.pos().is_near_to(x)))
+        .collect()
+}
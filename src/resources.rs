@@ -0,0 +1,148 @@
+//! Conversions between `ResourceType` and the game's resource constants,
+//! e.g. `"energy"` or `"XGH2O"`, for memory and the console.
+
+use crate::codec::{Decode, Decoder, Encode, Encoder};
+use screeps::ResourceType;
+
+/// Every resource type. Stored data refers to resources by index here, so
+/// new entries go at the end.
+const RESOURCE_STRINGS: [(ResourceType, &'static str); 84] = [
+    (ResourceType::Energy, "energy"),
+    (ResourceType::Power, "power"),
+    (ResourceType::Hydrogen, "H"),
+    (ResourceType::Oxygen, "O"),
+    (ResourceType::Utrium, "U"),
+    (ResourceType::Lemergium, "L"),
+    (ResourceType::Keanium, "K"),
+    (ResourceType::Zynthium, "Z"),
+    (ResourceType::Catalyst, "X"),
+    (ResourceType::Ghodium, "G"),
+    (ResourceType::Hydroxide, "OH"),
+    (ResourceType::ZynthiumKeanite, "ZK"),
+    (ResourceType::UtriumLemergite, "UL"),
+    (ResourceType::UtriumHydride, "UH"),
+    (ResourceType::UtriumOxide, "UO"),
+    (ResourceType::KeaniumHydride, "KH"),
+    (ResourceType::KeaniumOxide, "KO"),
+    (ResourceType::LemergiumHydride, "LH"),
+    (ResourceType::LemergiumOxide, "LO"),
+    (ResourceType::ZynthiumHydride, "ZH"),
+    (ResourceType::ZynthiumOxide, "ZO"),
+    (ResourceType::GhodiumHydride, "GH"),
+    (ResourceType::GhodiumOxide, "GO"),
+    (ResourceType::UtriumAcid, "UH2O"),
+    (ResourceType::UtriumAlkalide, "UHO2"),
+    (ResourceType::KeaniumAcid, "KH2O"),
+    (ResourceType::KeaniumAlkalide, "KHO2"),
+    (ResourceType::LemergiumAcid, "LH2O"),
+    (ResourceType::LemergiumAlkalide, "LHO2"),
+    (ResourceType::ZynthiumAcid, "ZH2O"),
+    (ResourceType::ZynthiumAlkalide, "ZHO2"),
+    (ResourceType::GhodiumAcid, "GH2O"),
+    (ResourceType::GhodiumAlkalide, "GHO2"),
+    (ResourceType::CatalyzedUtriumAcid, "XUH2O"),
+    (ResourceType::CatalyzedUtriumAlkalide, "XUHO2"),
+    (ResourceType::CatalyzedKeaniumAcid, "XKH2O"),
+    (ResourceType::CatalyzedKeaniumAlkalide, "XKHO2"),
+    (ResourceType::CatalyzedLemergiumAcid, "XLH2O"),
+    (ResourceType::CatalyzedLemergiumAlkalide, "XLHO2"),
+    (ResourceType::CatalyzedZynthiumAcid, "XZH2O"),
+    (ResourceType::CatalyzedZynthiumAlkalide, "XZHO2"),
+    (ResourceType::CatalyzedGhodiumAcid, "XGH2O"),
+    (ResourceType::CatalyzedGhodiumAlkalide, "XGHO2"),
+    (ResourceType::Ops, "ops"),
+    (ResourceType::UtriumBar, "utrium_bar"),
+    (ResourceType::LemergiumBar, "lemergium_bar"),
+    (ResourceType::ZynthiumBar, "zynthium_bar"),
+    (ResourceType::KeaniumBar, "keanium_bar"),
+    (ResourceType::GhodiumMelt, "ghodium_melt"),
+    (ResourceType::Oxidant, "oxidant"),
+    (ResourceType::Reductant, "reductant"),
+    (ResourceType::Purifier, "purifier"),
+    (ResourceType::Battery, "battery"),
+    (ResourceType::Composite, "composite"),
+    (ResourceType::Crystal, "crystal"),
+    (ResourceType::Liquid, "liquid"),
+    (ResourceType::Silicon, "silicon"),
+    (ResourceType::Metal, "metal"),
+    (ResourceType::Biomass, "biomass"),
+    (ResourceType::Mist, "mist"),
+    (ResourceType::Wire, "wire"),
+    (ResourceType::Cell, "cell"),
+    (ResourceType::Alloy, "alloy"),
+    (ResourceType::Condensate, "condensate"),
+    (ResourceType::Switch, "switch"),
+    (ResourceType::Transistor, "transistor"),
+    (ResourceType::Microchip, "microchip"),
+    (ResourceType::Circuit, "circuit"),
+    (ResourceType::Device, "device"),
+    (ResourceType::Phlegm, "phlegm"),
+    (ResourceType::Tissue, "tissue"),
+    (ResourceType::Muscle, "muscle"),
+    (ResourceType::Organoid, "organoid"),
+    (ResourceType::Organism, "organism"),
+    (ResourceType::Tube, "tube"),
+    (ResourceType::Fixtures, "fixtures"),
+    (ResourceType::Frame, "frame"),
+    (ResourceType::Hydraulics, "hydraulics"),
+    (ResourceType::Machine, "machine"),
+    (ResourceType::Concentrate, "concentrate"),
+    (ResourceType::Extract, "extract"),
+    (ResourceType::Spirit, "spirit"),
+    (ResourceType::Emanation, "emanation"),
+    (ResourceType::Essence, "essence"),
+];
+
+pub fn to_string(resource: ResourceType) -> String {
+    for (resource_type, resource_string) in RESOURCE_STRINGS.iter() {
+        if *resource_type == resource {
+            return resource_string.to_string();
+        }
+    }
+    return format!("{:?}", resource);
+}
+
+pub fn from_str(s: &str) -> Option<ResourceType> {
+    for (resource_type, resource_string) in RESOURCE_STRINGS.iter() {
+        if *resource_string == s {
+            return Some(*resource_type);
+        }
+    }
+    return None;
+}
+
+impl Encode for ResourceType {
+    fn encode(&self, encoder: &mut Encoder) {
+        let index = RESOURCE_STRINGS
+            .iter()
+            .position(|(resource_type, _)| resource_type == self)
+            .expect("expected every stored resource type to have a string");
+        encoder.write_varint(index as u32);
+    }
+}
+
+impl Decode for ResourceType {
+    fn decode(decoder: &mut Decoder) -> Option<Self> {
+        let (resource_type, _) = RESOURCE_STRINGS.get(decoder.read_varint()? as usize)?;
+        Some(*resource_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_resource_type_has_its_game_string() {
+        for (resource_type, resource_string) in RESOURCE_STRINGS.iter() {
+            assert_eq!(resource_string.parse().ok(), Some(*resource_type));
+            assert_eq!(to_string(*resource_type), *resource_string);
+            assert_eq!(from_str(resource_string), Some(*resource_type));
+        }
+        let mut resource_types: Vec<u32> =
+            RESOURCE_STRINGS.iter().map(|(x, _)| *x as u32).collect();
+        resource_types.sort();
+        resource_types.dedup();
+        assert_eq!(resource_types, (1..=84).collect::<Vec<u32>>());
+    }
+}
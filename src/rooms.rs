@@ -0,0 +1,11 @@
+//! Helpers for per-room state kept in `Memory.rooms`.
+
+use screeps::memory::MemoryReference;
+
+pub fn memory(room_name: &str) -> Option<MemoryReference> {
+    screeps::memory::root()
+        .dict_or_create("rooms")
+        .ok()?
+        .dict_or_create(room_name)
+        .ok()
+}
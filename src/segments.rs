@@ -0,0 +1,212 @@
+//! Named storage on top of `RawMemory.segments`.
+//!
+//! Segments are only readable on the tick after they were requested, and at
+//! most 10 can be active at once. Callers `read` a named value every tick
+//! they need it; the first call requests the segments and returns `None`
+//! until they become active. Values larger than a single segment are split
+//! across several segments.
+
+use log::*;
+use screeps::memory::MemoryReference;
+
+const MAX_ACTIVE_SEGMENTS: usize = 10;
+const MAX_SEGMENT_SIZE: usize = 100 * 1024;
+
+/// Segments from this id up are fixed-purpose and never allocated to names.
+pub const FIRST_RESERVED_SEGMENT: u32 = 90;
+
+pub fn read(name: &str) -> Option<String> {
+    request(name);
+
+    let ids = get_segment_ids(name);
+    if ids.is_empty() {
+        return None;
+    }
+
+    let mut result = String::new();
+    for id in ids {
+        result.push_str(&screeps::raw_memory::get_segment(id)?);
+    }
+    return Some(result);
+}
+
+pub fn write(name: &str, data: &str) {
+    let chunks = split_into_chunks(data);
+    let mut ids = get_segment_ids(name);
+    if ids.len() < chunks.len() {
+        match find_free_ids(&get_used_ids(), chunks.len() - ids.len()) {
+            Some(new_ids) => ids.extend(new_ids),
+            None => {
+                error!("out of memory segments, unable to write '{}'", name);
+                return;
+            }
+        }
+    }
+
+    for (id, chunk) in ids.iter().zip(chunks.iter()) {
+        screeps::raw_memory::set_segment(*id, chunk);
+    }
+    for id in &ids[chunks.len()..] {
+        screeps::raw_memory::set_segment(*id, "");
+    }
+    ids.truncate(chunks.len());
+    set_segment_ids(name, &ids);
+}
+
+/// True if a value has ever been written under this name.
+pub fn exists(name: &str) -> bool {
+    !get_segment_ids(name).is_empty()
+}
+
+/// Asks for the named segments to be active next tick.
+pub fn request(name: &str) {
+    let mut requested = get_requested();
+    if !requested.iter().any(|x| x == name) {
+        requested.push(name.to_string());
+        set_requested(&requested);
+    }
+}
+
+/// Activates the segments requested this tick. Requests that do not fit
+/// are carried over to the next tick.
+pub fn game_loop() {
+    let mut active_ids: Vec<u32> = vec![];
+    let mut deferred = vec![];
+
+    for name in get_requested() {
+        let ids = get_segment_ids(&name);
+        let new_ids: Vec<u32> = ids
+            .into_iter()
+            .filter(|x| !active_ids.contains(x))
+            .collect();
+        if active_ids.len() + new_ids.len() <= MAX_ACTIVE_SEGMENTS {
+            active_ids.extend(new_ids);
+        } else {
+            debug!("deferring segment request '{}'", name);
+            deferred.push(name);
+        }
+    }
+
+    screeps::raw_memory::set_active_segments(&active_ids);
+    set_requested(&deferred);
+}
+
+fn split_into_chunks(data: &str) -> Vec<String> {
+    let mut result = vec![];
+    let mut chunk = String::new();
+    let mut chunk_size = 0;
+    for c in data.chars() {
+        if chunk_size == MAX_SEGMENT_SIZE {
+            result.push(std::mem::replace(&mut chunk, String::new()));
+            chunk_size = 0;
+        }
+        chunk.push(c);
+        chunk_size += 1;
+    }
+    result.push(chunk);
+    return result;
+}
+
+/// Ids of the segments allocated to any name.
+fn get_used_ids() -> Vec<u32> {
+    let mut result = vec![];
+    if let Some(table) = get_table() {
+        for name in table.keys() {
+            result.extend(get_segment_ids(&name));
+        }
+    }
+    return result;
+}
+
+/// `count` distinct segment ids that aren't in `used_ids` or reserved.
+fn find_free_ids(used_ids: &[u32], count: usize) -> Option<Vec<u32>> {
+    let result: Vec<u32> = (0..FIRST_RESERVED_SEGMENT)
+        .filter(|x| !used_ids.contains(x))
+        .take(count)
+        .collect();
+    if result.len() < count {
+        return None;
+    }
+    return Some(result);
+}
+
+fn get_segment_ids(name: &str) -> Vec<u32> {
+    let ids_string = get_table()
+        .and_then(|table| table.string(name).ok()?)
+        .unwrap_or_default();
+    return ids_string
+        .split(",")
+        .filter_map(|x| x.parse().ok())
+        .collect();
+}
+
+fn set_segment_ids(name: &str, ids: &[u32]) {
+    if let Some(table) = get_table() {
+        let ids_string: Vec<String> = ids.iter().map(|x| x.to_string()).collect();
+        table.set(name, ids_string.join(","));
+    }
+}
+
+fn get_requested() -> Vec<String> {
+    let requested_string = get_root()
+        .and_then(|root| root.string("requested").ok()?)
+        .unwrap_or_default();
+    return requested_string
+        .split(",")
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_string())
+        .collect();
+}
+
+fn set_requested(requested: &[String]) {
+    if let Some(root) = get_root() {
+        root.set("requested", requested.join(","));
+    }
+}
+
+fn get_table() -> Option<MemoryReference> {
+    get_root()?.dict_or_create("table").ok()
+}
+
+fn get_root() -> Option<MemoryReference> {
+    screeps::memory::root().dict_or_create("segments").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_values_fit_one_chunk() {
+        assert_eq!(split_into_chunks(""), vec![String::new()]);
+        assert_eq!(split_into_chunks("abc"), vec!["abc".to_string()]);
+    }
+
+    #[test]
+    fn large_values_are_split_by_characters() {
+        let data = "é".repeat(MAX_SEGMENT_SIZE * 2 + 1);
+        let chunks = split_into_chunks(&data);
+        let sizes: Vec<usize> = chunks.iter().map(|x| x.chars().count()).collect();
+        assert_eq!(sizes, vec![MAX_SEGMENT_SIZE, MAX_SEGMENT_SIZE, 1]);
+        assert_eq!(chunks.concat(), data);
+
+        let data = "a".repeat(MAX_SEGMENT_SIZE);
+        assert_eq!(split_into_chunks(&data).len(), 1);
+    }
+
+    #[test]
+    fn free_ids_are_distinct_and_unused() {
+        assert_eq!(find_free_ids(&[0, 2], 3), Some(vec![1, 3, 4]));
+        assert_eq!(find_free_ids(&[], 0), Some(vec![]));
+    }
+
+    #[test]
+    fn reserved_ids_are_never_free() {
+        let used_ids: Vec<u32> = (0..FIRST_RESERVED_SEGMENT - 1).collect();
+        assert_eq!(
+            find_free_ids(&used_ids, 1),
+            Some(vec![FIRST_RESERVED_SEGMENT - 1])
+        );
+        assert_eq!(find_free_ids(&used_ids, 2), None);
+    }
+}
@@ -1,6 +1,127 @@
 use crate::allocator;
+use crate::boosts;
+use crate::codec::{self, Decode, Decoder, Encode, Encoder};
+use crate::creeps::{self, Role};
+use crate::names;
+use crate::rooms;
 use log::*;
-use screeps::{prelude::*, ResourceType, ReturnCode};
+use screeps::{prelude::*, Part, ResourceType, ReturnCode};
+
+/// SPAWN_ENERGY_CAPACITY
+const SPAWN_ENERGY_CAPACITY: u32 = 300;
+/// Layout of the stored spawn queue. Version 0 requests predate `boosts`.
+const QUEUE_VERSION: u32 = 1;
+
+/// A creep waiting to be spawned in a room. Requests stay queued until they
+/// are spawned, so a spawn that is short of energy retries next tick.
+#[derive(Debug, Clone)]
+pub struct SpawnRequest {
+    pub role: Role,
+    /// Name of an expiring creep this one takes over from.
+    pub replaces: Option<String>,
+    /// Compounds to boost body parts with after spawning.
+    pub boosts: Vec<(Part, ResourceType)>,
+}
+
+impl SpawnRequest {
+    pub fn new(role: Role) -> SpawnRequest {
+        SpawnRequest {
+            role: role,
+            replaces: None,
+            boosts: vec![],
+        }
+    }
+}
+
+impl Encode for SpawnRequest {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.role.encode(encoder);
+        self.replaces.encode(encoder);
+        self.boosts.encode(encoder);
+    }
+}
+
+impl Decode for SpawnRequest {
+    fn decode(decoder: &mut Decoder) -> Option<Self> {
+        Some(SpawnRequest {
+            role: Role::decode(decoder)?,
+            replaces: Option::<String>::decode(decoder)?,
+            boosts: Decode::decode(decoder)?,
+        })
+    }
+}
+
+pub fn get_queue(room_name: &str) -> Vec<SpawnRequest> {
+    let memory = match rooms::memory(room_name) {
+        Some(memory) => memory,
+        None => return vec![],
+    };
+    if let Ok(Some(queue_string)) = memory.string("spawn_queue") {
+        if let Some(queue) = decode_queue(&queue_string) {
+            return queue;
+        }
+        warn!("{}: discarding unreadable spawn queue", room_name);
+    }
+    return vec![];
+}
+
+fn decode_queue(data: &str) -> Option<Vec<SpawnRequest>> {
+    let (version, mut decoder) = codec::decode_versioned(data)?;
+    match version {
+        0 => {
+            let requests: Vec<(Role, Option<String>)> = Decode::decode(&mut decoder)?;
+            let result = requests
+                .into_iter()
+                .map(|(role, replaces)| SpawnRequest {
+                    role: role,
+                    replaces: replaces,
+                    boosts: vec![],
+                })
+                .collect();
+            return Some(result);
+        }
+        QUEUE_VERSION => return Decode::decode(&mut decoder),
+        _ => return None,
+    }
+}
+
+pub fn queue_request(room_name: &str, request: SpawnRequest) {
+    let mut queue = get_queue(room_name);
+    queue.push(request);
+    set_queue(room_name, &queue);
+}
+
+/// Records why the front of the queue last failed to spawn, for `bot.report`.
+fn set_spawn_status(room_name: &str, failure: Option<(&SpawnRequest, ReturnCode)>) {
+    if let Some(memory) = rooms::memory(room_name) {
+        match failure {
+            Some((request, return_code)) => memory.set(
+                "spawn_status",
+                format!(
+                    "[{}] {}: {:?}",
+                    screeps::game::time(),
+                    request.role.to_string(),
+                    return_code
+                ),
+            ),
+            None => memory.del("spawn_status"),
+        }
+    }
+}
+
+pub fn get_spawn_status(room_name: &str) -> Option<String> {
+    rooms::memory(room_name)?.string("spawn_status").ok()?
+}
+
+fn set_queue(room_name: &str, queue: &Vec<SpawnRequest>) {
+    if let Some(memory) = rooms::memory(room_name) {
+        if queue.is_empty() {
+            memory.del("spawn_queue");
+        } else {
+            memory.set("spawn_queue", codec::encode_versioned(QUEUE_VERSION, queue));
+        }
+    }
+}
 
 struct Spawn(screeps::StructureSpawn);
 
@@ -12,27 +133,88 @@ impl Spawn {
             return;
         }
 
-        if let Some((body, role)) = allocator::get_spawn_target(self.capacity()) {
-            self.spawn_creep(&body, role.to_string());
+        let room = self.0.room();
+        let room_name = room.name().to_string();
+        let mut queue = get_queue(&room_name);
+        if queue.is_empty() {
+            if let Some((body, role)) = allocator::get_spawn_target(self.capacity()) {
+                let mut request = SpawnRequest::new(role);
+                request.boosts = boosts::get_boosts(&request.role, &room, &body);
+                queue.push(request);
+            }
         }
+
+        let request = match queue.first() {
+            Some(request) => request.clone(),
+            None => return,
+        };
+        let body = allocator::get_body(&request.role, self.capacity());
+        let return_code = self.spawn_creep(&body, &request, &room_name);
+        match return_code {
+            ReturnCode::Ok => {
+                queue.remove(0);
+                set_spawn_status(&room_name, None);
+            }
+            // Transient, keep the request at the front of the queue.
+            ReturnCode::NotEnough | ReturnCode::Busy | ReturnCode::NameExists => {
+                debug!("delaying {:?}: {:?}", request, return_code);
+                set_spawn_status(&room_name, Some((&request, return_code)));
+            }
+            _ => {
+                warn!("dropping {:?}: {:?}", request, return_code);
+                queue.remove(0);
+                set_spawn_status(&room_name, Some((&request, return_code)));
+            }
+        }
+        set_queue(&room_name, &queue);
     }
 
-    fn spawn_creep(&mut self, body: &Vec<screeps::Part>, name_prefix: &str) {
-        for i in 0..1000 {
-            let name = name_prefix.to_owned() + ":" + &i.to_string();
-            let return_code = self.0.spawn_creep(&body, &name);
-            match return_code {
-                ReturnCode::NameExists => continue,
-                ReturnCode::Ok => return,
-                _ => warn!("couldn't spawn: {:?}", return_code),
+    fn spawn_creep(
+        &mut self,
+        body: &Vec<screeps::Part>,
+        request: &SpawnRequest,
+        home: &str,
+    ) -> ReturnCode {
+        let name = names::next_name(&request.role, home);
+        let memory = screeps::memory::MemoryReference::new();
+        memory.set("role", request.role.to_string());
+        memory.set("home", home);
+        if let Some(replaces) = &request.replaces {
+            memory.set("replaces", replaces.as_str());
+        }
+        let options = screeps::SpawnOptions::new().memory(memory);
+        let return_code = self.0.spawn_creep_with_options(&body, &name, &options);
+
+        if return_code == ReturnCode::Ok {
+            if !request.boosts.is_empty() {
+                boosts::reserve(home, &name, &request.boosts, body);
+            }
+            if let Some(replaced_creep) = request
+                .replaces
+                .as_ref()
+                .and_then(|x| screeps::game::creeps::get(x))
+            {
+                replaced_creep.memory().set("successor", name.as_str());
             }
-            return;
         }
+        return return_code;
     }
 
     fn capacity(&self) -> u32 {
-        self.0.store_capacity(Some(ResourceType::Energy))
+        get_spawn_capacity(&self.0.room())
+    }
+}
+
+/// The energy to size new bodies for. Without harvesters the extensions
+/// won't be refilled, so fall back to what the room has right now.
+pub fn get_spawn_capacity(room: &screeps::Room) -> u32 {
+    let has_harvesters = creeps::all()
+        .iter()
+        .any(|x| x.role == Role::Harvester && x.room_name() == room.name());
+    if has_harvesters {
+        return room.energy_capacity_available();
     }
+    return room.energy_available().max(SPAWN_ENERGY_CAPACITY);
 }
 
 pub struct SpawnManager {
@@ -0,0 +1,189 @@
+//! Per tick metrics, written as a JSON tree to a fixed memory segment for
+//! a Grafana agent to collect.
+
+use crate::creeps;
+use crate::resources;
+use crate::segments::FIRST_RESERVED_SEGMENT;
+use screeps::{find, prelude::*};
+use stdweb::{js, unstable::TryInto};
+
+pub const STATS_SEGMENT: u32 = FIRST_RESERVED_SEGMENT + 9;
+
+enum Value {
+    Number(f64),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn write(&self, output: &mut String) {
+        match self {
+            Value::Number(number) => {
+                if number.is_finite() {
+                    output.push_str(&number.to_string());
+                } else {
+                    output.push_str("null");
+                }
+            }
+            Value::Object(fields) => {
+                output.push('{');
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        output.push(',');
+                    }
+                    output.push('"');
+                    output.push_str(&key.replace('\\', "\\\\").replace('"', "\\\""));
+                    output.push_str("\":");
+                    value.write(output);
+                }
+                output.push('}');
+            }
+        }
+    }
+}
+
+/// Collects this tick's metrics. Call last so CPU usage is complete.
+pub fn game_loop() {
+    let stats = Value::Object(vec![
+        ("time".to_string(), number(screeps::game::time())),
+        ("cpu".to_string(), cpu_stats()),
+        (
+            "gcl".to_string(),
+            progress_stats(
+                screeps::game::gcl::level() as f64,
+                screeps::game::gcl::progress() as f64,
+                screeps::game::gcl::progress_total() as f64,
+            ),
+        ),
+        (
+            "gpl".to_string(),
+            progress_stats(
+                get_gpl("level"),
+                get_gpl("progress"),
+                get_gpl("progressTotal"),
+            ),
+        ),
+        ("rooms".to_string(), rooms_stats()),
+        ("creeps".to_string(), creep_stats()),
+    ]);
+
+    let mut output = String::new();
+    stats.write(&mut output);
+    screeps::raw_memory::set_segment(STATS_SEGMENT, &output);
+}
+
+/// Reads a field of `Game.gpl`, which the bindings don't cover.
+fn get_gpl(field: &str) -> f64 {
+    let value = js! {
+        return Game.gpl[@{field}];
+    };
+    return value.try_into().unwrap_or(0.0);
+}
+
+fn number<T: Into<f64>>(value: T) -> Value {
+    Value::Number(value.into())
+}
+
+fn cpu_stats() -> Value {
+    Value::Object(vec![
+        (
+            "used".to_string(),
+            number(screeps::game::cpu::get_used() as f64),
+        ),
+        (
+            "bucket".to_string(),
+            number(screeps::game::cpu::bucket() as f64),
+        ),
+        (
+            "limit".to_string(),
+            number(screeps::game::cpu::limit() as f64),
+        ),
+    ])
+}
+
+fn progress_stats(level: f64, progress: f64, progress_total: f64) -> Value {
+    Value::Object(vec![
+        ("level".to_string(), number(level)),
+        ("progress".to_string(), number(progress)),
+        ("progressTotal".to_string(), number(progress_total)),
+    ])
+}
+
+fn rooms_stats() -> Value {
+    let mut result = vec![];
+    for room in screeps::game::rooms::values() {
+        let controller = match room.controller() {
+            Some(controller) if controller.my() => controller,
+            _ => continue,
+        };
+
+        let mut room_stats = vec![
+            (
+                "rcl".to_string(),
+                progress_stats(
+                    controller.level() as f64,
+                    controller.progress().unwrap_or(0) as f64,
+                    controller.progress_total().unwrap_or(0) as f64,
+                ),
+            ),
+            (
+                "energyAvailable".to_string(),
+                number(room.energy_available()),
+            ),
+            (
+                "energyCapacityAvailable".to_string(),
+                number(room.energy_capacity_available()),
+            ),
+            ("spawns".to_string(), spawn_stats(&room)),
+            ("construction".to_string(), construction_stats(&room)),
+        ];
+        if let Some(storage) = room.storage() {
+            let contents = storage
+                .store_types()
+                .iter()
+                .map(|x| (resources::to_string(*x), number(storage.store_of(*x))))
+                .collect();
+            room_stats.push(("storage".to_string(), Value::Object(contents)));
+        }
+
+        result.push((room.name().to_string(), Value::Object(room_stats)));
+    }
+    return Value::Object(result);
+}
+
+fn spawn_stats(room: &screeps::Room) -> Value {
+    let spawns = room.find(find::MY_SPAWNS);
+    let busy = spawns.iter().filter(|x| x.is_spawning()).count();
+    let utilization = if spawns.is_empty() {
+        0.0
+    } else {
+        busy as f64 / spawns.len() as f64
+    };
+    Value::Object(vec![
+        ("count".to_string(), number(spawns.len() as f64)),
+        ("busy".to_string(), number(busy as f64)),
+        ("utilization".to_string(), number(utilization)),
+    ])
+}
+
+fn construction_stats(room: &screeps::Room) -> Value {
+    let sites = room.find(find::MY_CONSTRUCTION_SITES);
+    let progress: u32 = sites.iter().map(|x| x.progress()).sum();
+    let progress_total: u32 = sites.iter().map(|x| x.progress_total()).sum();
+    Value::Object(vec![
+        ("sites".to_string(), number(sites.len() as f64)),
+        ("progress".to_string(), number(progress)),
+        ("progressTotal".to_string(), number(progress_total)),
+    ])
+}
+
+fn creep_stats() -> Value {
+    let mut counts: Vec<(String, Value)> = vec![];
+    for creep in creeps::all() {
+        let role = creep.role.to_string();
+        match counts.iter_mut().find(|(key, _)| *key == role) {
+            Some((_, Value::Number(count))) => *count += 1.0,
+            _ => counts.push((role.to_string(), number(1))),
+        }
+    }
+    return Value::Object(counts);
+}
@@ -0,0 +1,501 @@
+//! Keeps each terminal near a target amount of every resource.
+//!
+//! A terminal with more than its target first sends the surplus to another
+//! of our terminals that is short, then sells what's left of base minerals
+//! and finished commodities to the best buy order. Terminals well short of a resource buy
+//! it when a sell order is under the price limit. Each terminal does at most
+//! one of these per cooldown, and deals are logged in `Memory.market.deals`.
+//! Reading the order book is expensive, so trades are only looked for every
+//! `TRADE_INTERVAL` ticks.
+
+use crate::market::{GameMarket, Market, MarketOrder, OrderType};
+use crate::resources;
+use log::*;
+use screeps::{prelude::*, ResourceType, ReturnCode, StructureTerminal};
+
+const ENERGY_TARGET: u32 = 30000;
+const BASE_MINERAL_TARGET: u32 = 6000;
+const DEFAULT_TARGET: u32 = 3000;
+const MIN_DEAL_AMOUNT: u32 = 500;
+const MAX_DEAL_AMOUNT: u32 = 5000;
+const TRADE_INTERVAL: u32 = 20;
+/// Credits an energy is taken to be worth when weighing transaction costs.
+const ENERGY_VALUE: f64 = 0.05;
+/// Purchases never spend the last of these credits.
+const MIN_CREDITS: f64 = 10000.0;
+/// Resources worth buying when short, and the most to pay per unit
+/// including the energy spent on the transfer.
+const BUY_PRICE_LIMITS: [(ResourceType, f64); 8] = [
+    (ResourceType::Energy, 0.1),
+    (ResourceType::Hydrogen, 0.5),
+    (ResourceType::Oxygen, 0.5),
+    (ResourceType::Utrium, 0.5),
+    (ResourceType::Lemergium, 0.5),
+    (ResourceType::Keanium, 0.5),
+    (ResourceType::Zynthium, 0.5),
+    (ResourceType::Catalyst, 0.8),
+];
+const MAX_LOGGED_DEALS: usize = 20;
+
+/// Factory products nothing else uses, made for the market. Power, ops, bars
+/// and lab compounds are kept for the power spawn, factory and labs.
+const SOLD_COMMODITIES: [ResourceType; 7] = [
+    ResourceType::Wire,
+    ResourceType::Cell,
+    ResourceType::Alloy,
+    ResourceType::Condensate,
+    ResourceType::Composite,
+    ResourceType::Crystal,
+    ResourceType::Liquid,
+];
+
+const BASE_MINERALS: [ResourceType; 7] = [
+    ResourceType::Hydrogen,
+    ResourceType::Oxygen,
+    ResourceType::Utrium,
+    ResourceType::Lemergium,
+    ResourceType::Keanium,
+    ResourceType::Zynthium,
+    ResourceType::Catalyst,
+];
+
+/// A snapshot of a terminal's contents.
+#[derive(Debug, Clone)]
+pub struct TerminalState {
+    pub room_name: String,
+    pub store: Vec<(ResourceType, u32)>,
+}
+
+impl TerminalState {
+    fn new(terminal: &StructureTerminal) -> TerminalState {
+        TerminalState {
+            room_name: terminal.room().name().to_string(),
+            store: terminal
+                .store_types()
+                .into_iter()
+                .map(|x| (x, terminal.store_of(x)))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, resource: ResourceType) -> u32 {
+        self.store
+            .iter()
+            .find(|(x, _)| *x == resource)
+            .map_or(0, |(_, amount)| *amount)
+    }
+}
+
+/// A deal the terminal in `room_name` should make.
+#[derive(Debug, Clone)]
+pub struct Trade {
+    pub order: MarketOrder,
+    pub amount: u32,
+    pub room_name: String,
+    pub energy_cost: u32,
+}
+
+pub fn game_loop() {
+    let terminals: Vec<StructureTerminal> = screeps::game::rooms::values()
+        .into_iter()
+        .filter(|x| x.controller().map_or(false, |x| x.my()))
+        .filter_map(|x| x.terminal())
+        .filter(|x| x.my())
+        .collect();
+    let states: Vec<TerminalState> = terminals.iter().map(TerminalState::new).collect();
+
+    let mut market = GameMarket;
+    let can_trade = screeps::game::time() % TRADE_INTERVAL == 0;
+    for (terminal, state) in terminals.iter().zip(states.iter()) {
+        if terminal.cooldown() > 0 {
+            continue;
+        }
+        if let Some((resource, amount, destination)) = find_transfer(state, &states) {
+            send(terminal, resource, amount, &destination);
+            continue;
+        }
+        if !can_trade {
+            continue;
+        }
+        if let Some(trade) = find_trade(&market, state) {
+            execute_trade(&mut market, &trade);
+        }
+    }
+}
+
+pub fn get_target(resource: ResourceType) -> u32 {
+    if resource == ResourceType::Energy {
+        return ENERGY_TARGET;
+    }
+    if BASE_MINERALS.contains(&resource) {
+        return BASE_MINERAL_TARGET;
+    }
+    return DEFAULT_TARGET;
+}
+
+/// Surplus that another of our terminals is short of.
+fn find_transfer(
+    state: &TerminalState,
+    states: &[TerminalState],
+) -> Option<(ResourceType, u32, String)> {
+    for (resource, amount) in &state.store {
+        let surplus = amount.saturating_sub(get_target(*resource));
+        if surplus < MIN_DEAL_AMOUNT {
+            continue;
+        }
+        for other in states.iter().filter(|x| x.room_name != state.room_name) {
+            let needed = get_target(*resource).saturating_sub(other.get(*resource));
+            if needed >= MIN_DEAL_AMOUNT {
+                return Some((
+                    *resource,
+                    std::cmp::min(surplus, needed),
+                    other.room_name.clone(),
+                ));
+            }
+        }
+    }
+    return None;
+}
+
+/// Sells surplus minerals, or else buys what the terminal is short of.
+pub fn find_trade(market: &dyn Market, state: &TerminalState) -> Option<Trade> {
+    for (resource, amount) in &state.store {
+        if !BASE_MINERALS.contains(resource) && !SOLD_COMMODITIES.contains(resource) {
+            continue;
+        }
+        let surplus = amount.saturating_sub(get_target(*resource));
+        if surplus < MIN_DEAL_AMOUNT {
+            continue;
+        }
+        if let Some(trade) = find_sale(market, state, *resource, surplus) {
+            return Some(trade);
+        }
+    }
+
+    for (resource, price_limit) in BUY_PRICE_LIMITS.iter() {
+        let target = get_target(*resource);
+        let amount = state.get(*resource);
+        if amount >= target / 2 {
+            continue;
+        }
+        if let Some(trade) = find_purchase(market, state, *resource, target - amount, *price_limit)
+        {
+            return Some(trade);
+        }
+    }
+    return None;
+}
+
+/// The buy order paying the most once the energy to ship to it is counted.
+fn find_sale(
+    market: &dyn Market,
+    state: &TerminalState,
+    resource: ResourceType,
+    surplus: u32,
+) -> Option<Trade> {
+    let energy = state.get(ResourceType::Energy);
+    let mut best: Option<(f64, Trade)> = None;
+    for order in market.get_orders(resource, OrderType::Buy) {
+        let trade = match get_trade(market, state, order, surplus) {
+            Some(trade) => trade,
+            None => continue,
+        };
+        if trade.energy_cost > energy {
+            continue;
+        }
+        let net_price =
+            trade.order.price - trade.energy_cost as f64 * ENERGY_VALUE / trade.amount as f64;
+        if net_price <= 0.0 {
+            continue;
+        }
+        if best
+            .as_ref()
+            .map_or(true, |(best_price, _)| net_price > *best_price)
+        {
+            best = Some((net_price, trade));
+        }
+    }
+    return best.map(|(_, trade)| trade);
+}
+
+/// The cheapest sell order under the price limit once the energy to ship
+/// from it is counted.
+fn find_purchase(
+    market: &dyn Market,
+    state: &TerminalState,
+    resource: ResourceType,
+    needed: u32,
+    price_limit: f64,
+) -> Option<Trade> {
+    let energy = state.get(ResourceType::Energy);
+    let credits = market.get_credits() - MIN_CREDITS;
+    let mut best: Option<(f64, Trade)> = None;
+    for order in market.get_orders(resource, OrderType::Sell) {
+        let trade = match get_trade(market, state, order, needed) {
+            Some(trade) => trade,
+            None => continue,
+        };
+        if trade.energy_cost > energy || trade.order.price * trade.amount as f64 > credits {
+            continue;
+        }
+        let total_price =
+            trade.order.price + trade.energy_cost as f64 * ENERGY_VALUE / trade.amount as f64;
+        if total_price > price_limit {
+            continue;
+        }
+        if best
+            .as_ref()
+            .map_or(true, |(best_price, _)| total_price < *best_price)
+        {
+            best = Some((total_price, trade));
+        }
+    }
+    return best.map(|(_, trade)| trade);
+}
+
+fn get_trade(
+    market: &dyn Market,
+    state: &TerminalState,
+    order: MarketOrder,
+    wanted: u32,
+) -> Option<Trade> {
+    let order_room = order.room_name.clone()?;
+    let amount = wanted.min(order.remaining_amount).min(MAX_DEAL_AMOUNT);
+    if amount == 0 {
+        return None;
+    }
+    Some(Trade {
+        energy_cost: market.get_transaction_cost(amount, &state.room_name, &order_room),
+        order: order,
+        amount: amount,
+        room_name: state.room_name.clone(),
+    })
+}
+
+fn execute_trade(market: &mut dyn Market, trade: &Trade) {
+    let return_code = market.deal(&trade.order.id, trade.amount, &trade.room_name);
+    let description = format!(
+        "{} {} {} at {} in {} (energy {})",
+        match trade.order.order_type {
+            OrderType::Buy => "sold",
+            OrderType::Sell => "bought",
+        },
+        trade.amount,
+        resources::to_string(trade.order.resource),
+        trade.order.price,
+        trade.room_name,
+        trade.energy_cost
+    );
+    if return_code == ReturnCode::Ok {
+        info!("{}", description);
+        record_deal(&description);
+    } else {
+        warn!("failed to deal, {}: {:?}", description, return_code);
+    }
+}
+
+fn send(terminal: &StructureTerminal, resource: ResourceType, amount: u32, destination: &str) {
+    let return_code = terminal.send(resource, amount, destination, None);
+    let resource_string = resources::to_string(resource);
+    if return_code == ReturnCode::Ok {
+        info!(
+            "sent {} {} from {} to {}",
+            amount,
+            resource_string,
+            terminal.room().name(),
+            destination
+        );
+    } else {
+        warn!(
+            "failed to send {} {} from {} to {}: {:?}",
+            amount,
+            resource_string,
+            terminal.room().name(),
+            destination,
+            return_code
+        );
+    }
+}
+
+fn record_deal(description: &str) {
+    let deals = match screeps::memory::root()
+        .dict_or_create("market")
+        .and_then(|x| x.dict_or_create("deals"))
+    {
+        Ok(deals) => deals,
+        Err(_) => return,
+    };
+    deals.set(&screeps::game::time().to_string(), description);
+    let mut times: Vec<u32> = deals.keys().iter().filter_map(|x| x.parse().ok()).collect();
+    times.sort();
+    for old_time in times.iter().rev().skip(MAX_LOGGED_DEALS) {
+        deals.del(&old_time.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market::OrderBook;
+
+    const FAR_ROOM: &str = "W10N10";
+
+    fn get_state(room_name: &str, store: &[(ResourceType, u32)]) -> TerminalState {
+        TerminalState {
+            room_name: room_name.to_string(),
+            store: store.to_vec(),
+        }
+    }
+
+    fn get_order(id: &str, order_type: OrderType, room_name: &str, price: f64) -> MarketOrder {
+        MarketOrder {
+            id: id.to_string(),
+            order_type: order_type,
+            resource: ResourceType::Hydrogen,
+            room_name: Some(room_name.to_string()),
+            remaining_amount: 10000,
+            price: price,
+        }
+    }
+
+    /// Sending to `FAR_ROOM` costs an energy per unit, anywhere else a tenth.
+    fn get_market(orders: Vec<MarketOrder>) -> OrderBook {
+        OrderBook {
+            orders: orders,
+            credits: 1000000.0,
+            transaction_cost: |amount, _, to| {
+                if to == FAR_ROOM {
+                    amount
+                } else {
+                    amount / 10
+                }
+            },
+            deals: vec![],
+        }
+    }
+
+    #[test]
+    fn transfer_sends_surplus_to_short_terminal() {
+        let state = get_state("W1N1", &[(ResourceType::Hydrogen, 9000)]);
+        let states = [
+            state.clone(),
+            get_state("W2N1", &[(ResourceType::Hydrogen, 5800)]),
+            get_state("W3N1", &[]),
+        ];
+        assert_eq!(
+            find_transfer(&state, &states),
+            Some((ResourceType::Hydrogen, 3000, "W3N1".to_string()))
+        );
+    }
+
+    #[test]
+    fn transfer_ignores_small_surplus() {
+        let state = get_state("W1N1", &[(ResourceType::Hydrogen, 6400)]);
+        let states = [state.clone(), get_state("W2N1", &[])];
+        assert_eq!(find_transfer(&state, &states), None);
+    }
+
+    #[test]
+    fn sale_counts_energy_cost() {
+        let state = get_state(
+            "W1N1",
+            &[
+                (ResourceType::Energy, 30000),
+                (ResourceType::Hydrogen, 9000),
+            ],
+        );
+        let market = get_market(vec![
+            get_order("far", OrderType::Buy, FAR_ROOM, 1.0),
+            get_order("near", OrderType::Buy, "W2N1", 0.98),
+        ]);
+        let trade = find_trade(&market, &state).unwrap();
+        assert_eq!(trade.order.id, "near");
+        assert_eq!(trade.amount, 3000);
+        assert_eq!(trade.energy_cost, 300);
+    }
+
+    #[test]
+    fn only_minerals_and_commodities_are_sold() {
+        let state = get_state(
+            "W1N1",
+            &[
+                (ResourceType::Energy, 30000),
+                (ResourceType::Power, 9000),
+                (ResourceType::CatalyzedGhodiumAcid, 9000),
+                (ResourceType::UtriumBar, 9000),
+            ],
+        );
+        let mut orders = vec![];
+        for resource in [
+            ResourceType::Power,
+            ResourceType::CatalyzedGhodiumAcid,
+            ResourceType::UtriumBar,
+        ]
+        .iter()
+        {
+            let mut order = get_order("buy", OrderType::Buy, "W2N1", 10.0);
+            order.resource = *resource;
+            orders.push(order);
+        }
+        assert!(find_trade(&get_market(orders), &state).is_none());
+    }
+
+    #[test]
+    fn sale_needs_energy_for_transaction() {
+        let state = get_state(
+            "W1N1",
+            &[
+                (ResourceType::Energy, 20000),
+                (ResourceType::Hydrogen, 9000),
+            ],
+        );
+        let market = get_market(vec![get_order("far", OrderType::Buy, FAR_ROOM, 1.0)]);
+        assert!(find_trade(&market, &state).is_some());
+
+        let state = get_state(
+            "W1N1",
+            &[(ResourceType::Energy, 2000), (ResourceType::Hydrogen, 9000)],
+        );
+        assert!(find_trade(&market, &state).is_none());
+    }
+
+    #[test]
+    fn purchase_stays_under_price_limit() {
+        let state = get_state("W1N1", &[(ResourceType::Energy, 30000)]);
+        let market = get_market(vec![
+            get_order("expensive", OrderType::Sell, "W2N1", 0.6),
+            get_order("far", OrderType::Sell, FAR_ROOM, 0.46),
+            get_order("near", OrderType::Sell, "W2N1", 0.48),
+        ]);
+        let trade = find_trade(&market, &state).unwrap();
+        assert_eq!(trade.order.id, "near");
+        assert_eq!(trade.amount, MAX_DEAL_AMOUNT);
+
+        let market = get_market(vec![
+            get_order("expensive", OrderType::Sell, "W2N1", 0.6),
+            get_order("far", OrderType::Sell, FAR_ROOM, 0.46),
+        ]);
+        assert!(find_trade(&market, &state).is_none());
+    }
+
+    #[test]
+    fn purchase_keeps_minimum_credits() {
+        let state = get_state("W1N1", &[(ResourceType::Energy, 30000)]);
+        let mut market = get_market(vec![get_order("near", OrderType::Sell, "W2N1", 0.4)]);
+        market.credits = MIN_CREDITS + 100.0;
+        assert!(find_trade(&market, &state).is_none());
+    }
+
+    #[test]
+    fn no_purchase_above_half_of_target() {
+        let state = get_state(
+            "W1N1",
+            &[
+                (ResourceType::Energy, 30000),
+                (ResourceType::Hydrogen, 3000),
+            ],
+        );
+        let market = get_market(vec![get_order("near", OrderType::Sell, "W2N1", 0.4)]);
+        assert!(find_trade(&market, &state).is_none());
+    }
+}